@@ -0,0 +1,9 @@
+-- Images are only a cache of dimensions, served paths are not known here
+DROP TABLE IF EXISTS images;
+CREATE TABLE images (
+  id                 VARCHAR (128)   NOT NULL PRIMARY KEY,
+  modified_on_disk   DATETIME        NOT NULL,
+  width              INTEGER         NOT NULL,
+  height             INTEGER         NOT NULL,
+  local_path         VARCHAR (2048)  NOT NULL UNIQUE
+);
@@ -0,0 +1,10 @@
+DROP TABLE IF EXISTS resources;
+CREATE TABLE resources (
+  id                   VARCHAR (128)   NOT NULL PRIMARY KEY,
+  hash                 VARCHAR (64)    NOT NULL,
+  modified_on_disk     DATETIME        NOT NULL,
+  local_path           VARCHAR (2048)  NOT NULL UNIQUE,
+
+  -- Files with the same name and contents share the server path
+  server_path          VARCHAR (2048)  NOT NULL
+);
@@ -0,0 +1,2 @@
+ALTER TABLE articles ADD COLUMN hash VARCHAR (64) NOT NULL DEFAULT "";
+ALTER TABLE pages ADD COLUMN hash VARCHAR (64) NOT NULL DEFAULT "";
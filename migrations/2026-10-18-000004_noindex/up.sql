@@ -0,0 +1,2 @@
+ALTER TABLE articles ADD COLUMN noindex BOOLEAN NOT NULL DEFAULT 0;
+ALTER TABLE pages ADD COLUMN noindex BOOLEAN NOT NULL DEFAULT 0;
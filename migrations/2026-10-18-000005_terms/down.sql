@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS article_terms;
+DROP TABLE IF EXISTS terms;
@@ -0,0 +1,12 @@
+CREATE TABLE terms (
+  id                   VARCHAR (128)   NOT NULL PRIMARY KEY,
+  kind                 VARCHAR (16)    NOT NULL,
+  name                 VARCHAR (2048)  NOT NULL,
+  server_path          VARCHAR (2048)  NOT NULL UNIQUE
+);
+
+CREATE TABLE article_terms (
+  article_id           VARCHAR (128)   NOT NULL,
+  term_id              VARCHAR (128)   NOT NULL,
+  PRIMARY KEY (article_id, term_id)
+);
@@ -0,0 +1,4 @@
+ALTER TABLE articles ADD COLUMN draft BOOLEAN NOT NULL DEFAULT 0;
+
+-- Stored articles are compiled again to fill in the new column
+UPDATE articles SET hash = '';
@@ -0,0 +1 @@
+ALTER TABLE articles ADD COLUMN old_url VARCHAR (2048);
@@ -0,0 +1,30 @@
+-- SQLite can't drop columns, the tables are re-created instead
+DROP TABLE IF EXISTS articles;
+CREATE TABLE articles (
+  id                   VARCHAR (128)   NOT NULL PRIMARY KEY,
+  published            DATETIME        NOT NULL,
+  modified             DATETIME        NOT NULL,
+  modified_on_disk     DATETIME        NOT NULL,
+  local_path           VARCHAR (2048)  NOT NULL UNIQUE,
+  server_path          VARCHAR (2048)  NOT NULL UNIQUE,
+  title                VARCHAR (2048)  NOT NULL DEFAULT "",
+  html                 VARCHAR (10048) NOT NULL DEFAULT "",
+  hash                 VARCHAR (64)    NOT NULL DEFAULT "",
+  noindex              BOOLEAN         NOT NULL DEFAULT 0,
+  draft                BOOLEAN         NOT NULL DEFAULT 0,
+  old_url              VARCHAR (2048)
+);
+
+DROP TABLE IF EXISTS pages;
+CREATE TABLE pages (
+  id                   VARCHAR (128)   NOT NULL PRIMARY KEY,
+  published            DATETIME        NOT NULL,
+  modified             DATETIME        NOT NULL,
+  modified_on_disk     DATETIME        NOT NULL,
+  local_path           VARCHAR (2048)  NOT NULL UNIQUE,
+  server_path          VARCHAR (2048)  NOT NULL UNIQUE,
+  title                VARCHAR (2048)  NOT NULL DEFAULT "",
+  html                 VARCHAR (10048) NOT NULL DEFAULT "",
+  hash                 VARCHAR (64)    NOT NULL DEFAULT "",
+  noindex              BOOLEAN         NOT NULL DEFAULT 0
+);
@@ -0,0 +1,6 @@
+ALTER TABLE articles ADD COLUMN toc VARCHAR (10048) NOT NULL DEFAULT "";
+ALTER TABLE pages ADD COLUMN toc VARCHAR (10048) NOT NULL DEFAULT "";
+
+-- Stored documents are compiled again to fill in the new columns
+UPDATE articles SET hash = '';
+UPDATE pages SET hash = '';
@@ -0,0 +1,5 @@
+ALTER TABLE articles ADD COLUMN excerpt VARCHAR (10048) NOT NULL DEFAULT "";
+ALTER TABLE articles ADD COLUMN description VARCHAR (2048) NOT NULL DEFAULT "";
+
+-- Stored articles are compiled again to fill in the new columns
+UPDATE articles SET hash = '';
@@ -0,0 +1,8 @@
+ALTER TABLE articles ADD COLUMN word_count INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE articles ADD COLUMN reading_time INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE pages ADD COLUMN word_count INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE pages ADD COLUMN reading_time INTEGER NOT NULL DEFAULT 0;
+
+-- Stored documents are compiled again to fill in the new columns
+UPDATE articles SET hash = '';
+UPDATE pages SET hash = '';
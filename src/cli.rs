@@ -0,0 +1,69 @@
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+/// Static blog generator
+#[derive(Debug, StructOpt)]
+#[structopt(name = "cianticblog")]
+pub enum Command {
+    /// Generates the site to the output directory
+    Build(BuildOpts),
+
+    /// Generates the site, and regenerates it when the files change
+    Watch(BuildOpts),
+
+    /// Watches the files and serves the output directory with live reload
+    Serve {
+        #[structopt(flatten)]
+        opts: BuildOpts,
+
+        /// Address of the preview server
+        #[structopt(long, default_value = "127.0.0.1:8080")]
+        address: String,
+    },
+
+    /// Compiles all documents and checks their links, without writing output
+    Check(BuildOpts),
+
+    /// Creates a markdown file for a new article
+    New {
+        #[structopt(flatten)]
+        opts: BuildOpts,
+
+        /// Title of the article, the file is named by its slug
+        title: String,
+    },
+}
+
+/// Directories not given as flags are read from the site configuration, or
+/// default to the root directory
+#[derive(Debug, StructOpt)]
+pub struct BuildOpts {
+    /// Directory of the article markdown files, `articles` by default
+    #[structopt(long, parse(from_os_str))]
+    pub article_dir: Option<PathBuf>,
+
+    /// Directory of the page markdown files, `pages` by default
+    #[structopt(long, parse(from_os_str))]
+    pub pages_dir: Option<PathBuf>,
+
+    /// Directory of the generated site, `.out` by default
+    #[structopt(long, parse(from_os_str))]
+    pub output_dir: Option<PathBuf>,
+
+    /// Root directory with the `site.toml` configuration, links may not point
+    /// outside of it
+    #[structopt(long, parse(from_os_str), default_value = ".")]
+    pub root_dir: PathBuf,
+
+    /// Database caching the compiled documents, `.cache.db` by default
+    #[structopt(long, parse(from_os_str))]
+    pub db_file: Option<PathBuf>,
+
+    /// Keeps the existing files of the output directory
+    #[structopt(long)]
+    pub no_clean_output: bool,
+
+    /// Publishes the draft articles too
+    #[structopt(long)]
+    pub drafts: bool,
+}
@@ -0,0 +1,187 @@
+use async_std::fs;
+use async_std::path::PathBuf;
+use derive_more::From;
+use serde::Deserialize;
+use url::Url;
+
+#[derive(Debug, From)]
+pub enum Error {
+    IOError(std::io::Error),
+    TomlError(toml::de::Error),
+}
+
+/// Name of the configuration file in the root directory
+pub const CONFIG_FILE: &str = "site.toml";
+
+/// Site configuration, read from `site.toml` of the root directory
+///
+/// Directories are relative to the root directory, command-line flags
+/// override them.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SiteConfig {
+    /// Absolute URL of the site, e.g. `https://example.com/blog/`
+    pub base_url: Url,
+    pub site_name: String,
+    pub locale: String,
+
+    /// Author of the articles
+    pub author: String,
+
+    /// Default Open Graph image, absolute or relative to the base URL
+    pub image: Option<String>,
+
+    /// Number of articles on each page of the index
+    pub page_size: usize,
+
+    /// Color theme of the highlighted code, e.g. `base16-ocean.dark`
+    pub highlight_theme: String,
+
+    /// Articles and pages with at least this many headings show a table of
+    /// contents, 0 never shows it
+    pub toc_min_headings: usize,
+
+    pub search: SearchConfig,
+
+    pub article_dir: Option<std::path::PathBuf>,
+    pub pages_dir: Option<std::path::PathBuf>,
+    pub output_dir: Option<std::path::PathBuf>,
+    pub db_file: Option<std::path::PathBuf>,
+}
+
+impl Default for SiteConfig {
+    fn default() -> Self {
+        SiteConfig {
+            base_url: Url::parse("http://127.0.0.1:8080/").unwrap(),
+            site_name: "".into(),
+            locale: "en_US".into(),
+            author: "".into(),
+            image: None,
+            page_size: 10,
+            highlight_theme: "InspiredGitHub".into(),
+            toc_min_headings: 3,
+            search: SearchConfig::default(),
+            article_dir: None,
+            pages_dir: None,
+            output_dir: None,
+            db_file: None,
+        }
+    }
+}
+
+/// Common English words left out of the index by default
+const DEFAULT_STOP_WORDS: &[&str] = &[
+    "a", "an", "and", "are", "as", "at", "be", "but", "by", "for", "from", "has", "have", "he",
+    "i", "if", "in", "into", "is", "it", "its", "not", "of", "on", "or", "she", "so", "such",
+    "that", "the", "their", "then", "there", "these", "they", "this", "to", "was", "we", "were",
+    "will", "with", "you",
+];
+
+/// Search index settings, the `[search]` table of the configuration
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SearchConfig {
+    /// Most terms of each document in the index, the highest scoring ones
+    /// are kept
+    pub max_terms: usize,
+
+    /// Scores of the words in each field, 0 leaves the field out
+    pub title_weight: u32,
+    pub description_weight: u32,
+    pub tags_weight: u32,
+    pub body_weight: u32,
+
+    /// Words left out of the index
+    pub stop_words: Vec<String>,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        SearchConfig {
+            max_terms: 100,
+            title_weight: 10,
+            description_weight: 3,
+            tags_weight: 5,
+            body_weight: 1,
+            stop_words: DEFAULT_STOP_WORDS.iter().map(|&word| word.into()).collect(),
+        }
+    }
+}
+
+impl SiteConfig {
+    /// Absolute URL of the server path, e.g. `/articles/post01`
+    pub fn url(&self, server_path: &str) -> Url {
+        self.base_url
+            .join(server_path.trim_start_matches('/'))
+            .unwrap_or_else(|_| self.base_url.clone())
+    }
+
+    /// Absolute URL of the default Open Graph image
+    pub fn image_url(&self) -> Option<Url> {
+        self.image
+            .as_ref()
+            .map(|image| Url::parse(image).unwrap_or_else(|_| self.url(image)))
+    }
+}
+
+fn parse_config(contents: &str) -> Result<SiteConfig, Error> {
+    let mut config: SiteConfig = toml::from_str(contents)?;
+
+    // Base URL is a directory, otherwise joining would replace its last part
+    if !config.base_url.path().ends_with('/') {
+        let path = format!("{}/", config.base_url.path());
+        config.base_url.set_path(&path);
+    }
+    Ok(config)
+}
+
+/// Reads the configuration of the root directory, or the defaults if there
+/// is no configuration file
+pub async fn load_config(root_dir: &PathBuf) -> Result<SiteConfig, Error> {
+    let file = root_dir.join(CONFIG_FILE);
+    if !file.exists().await {
+        return Ok(SiteConfig::default());
+    }
+    parse_config(&fs::read_to_string(&file).await?)
+}
+
+#[cfg(test)]
+mod test_config {
+    use super::{parse_config, SiteConfig};
+
+    #[test]
+    fn test_parse_config() {
+        let config = parse_config(
+            &"
+            base_url = \"https://example.com/blog\"
+            site_name = \"My Thoughts\"
+            image = \"/resources/cover.png\"
+            article_dir = \"posts\"
+
+            [search]
+            body_weight = 2
+            "
+            .replace("            ", ""),
+        )
+        .unwrap();
+
+        assert_eq!(config.site_name, "My Thoughts");
+        assert_eq!(config.locale, SiteConfig::default().locale);
+        assert_eq!(config.article_dir, Some("posts".into()));
+        assert_eq!(config.search.body_weight, 2);
+        assert_eq!(config.search.title_weight, 10);
+        assert_eq!(
+            config.url("/articles/post01").as_str(),
+            "https://example.com/blog/articles/post01"
+        );
+        assert_eq!(
+            config.image_url().unwrap().as_str(),
+            "https://example.com/blog/resources/cover.png"
+        );
+    }
+
+    #[test]
+    fn test_parse_config_unknown_field() {
+        assert!(parse_config("base_ulr = \"https://example.com/\"").is_err());
+    }
+}
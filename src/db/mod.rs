@@ -15,11 +15,31 @@ pub enum Error {
     NotFound,
     MigrationError,
     ConnectionError,
+    ServerPathCollision {
+        server_path: String,
+        local_path: String,
+    },
     OtherDbError(diesel::result::Error),
 }
 
 pub type DbResult<T> = Result<T, Error>;
 
+/// Articles that are published in the output
+#[derive(Debug, Clone, Copy)]
+pub struct Published {
+    /// Includes the draft articles
+    pub drafts: bool,
+
+    /// Articles published later are scheduled, usually the time of the build
+    pub until: chrono::NaiveDateTime,
+}
+
+impl Published {
+    pub fn includes(&self, article: &models::Article) -> bool {
+        (self.drafts || !article.draft) && article.published <= self.until
+    }
+}
+
 #[derive(Clone)]
 pub struct DbConnection {
     pool: Pool<ConnectionManager<SqliteConnection>>,
@@ -133,6 +153,14 @@ generate_uuid_field! {
     ImageId
 }
 
+generate_uuid_field! {
+    ResourceId
+}
+
+generate_uuid_field! {
+    TermId
+}
+
 generate_uuid_field! {
     UrlId
 }
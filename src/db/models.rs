@@ -1,4 +1,4 @@
-use super::{ArticleId, ImageId, PageId, UrlId};
+use super::{ArticleId, ImageId, PageId, ResourceId, TermId, UrlId};
 use crate::db::schema::*;
 use chrono::NaiveDateTime;
 use chrono::Utc;
@@ -15,6 +15,30 @@ pub struct Article {
     pub server_path: String,
     pub title: String,
     pub html: String,
+    pub hash: String,
+
+    /// Hidden from search engines and the sitemap
+    pub noindex: bool,
+
+    /// Left out of the output, unless drafts are published
+    pub draft: bool,
+
+    /// Address of the article on the previous site, redirected to this one
+    pub old_url: Option<String>,
+
+    /// Table of contents as JSON, see `markdown::TocHeading`
+    pub toc: String,
+
+    /// Html shown in the listings, before the `<!-- more -->` marker or the
+    /// first paragraph
+    pub excerpt: String,
+
+    /// Plain text summary for the meta tags and feeds
+    pub description: String,
+    pub word_count: i32,
+
+    /// Estimated reading time in minutes
+    pub reading_time: i32,
 }
 
 #[derive(
@@ -29,6 +53,73 @@ pub struct Page {
     pub server_path: String,
     pub title: String,
     pub html: String,
+    pub hash: String,
+
+    /// Hidden from search engines and the sitemap
+    pub noindex: bool,
+
+    /// Table of contents as JSON, see `markdown::TocHeading`
+    pub toc: String,
+    pub word_count: i32,
+
+    /// Estimated reading time in minutes
+    pub reading_time: i32,
+}
+
+#[cfg(test)]
+impl Article {
+    /// Article served from the server path and published at noon on 1
+    /// January 2021, with the other fields empty
+    pub fn test(server_path: &str) -> Article {
+        let date = chrono::NaiveDate::from_ymd_opt(2021, 1, 1)
+            .and_then(|date| date.and_hms_opt(12, 0, 0))
+            .unwrap();
+        Article {
+            id: ArticleId::new(),
+            published: date,
+            modified: date,
+            modified_on_disk: date,
+            local_path: "".into(),
+            server_path: server_path.into(),
+            title: "".into(),
+            html: "".into(),
+            hash: "".into(),
+            noindex: false,
+            draft: false,
+            old_url: None,
+            toc: "".into(),
+            excerpt: "".into(),
+            description: "".into(),
+            word_count: 0,
+            reading_time: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+impl Page {
+    /// Page served from the server path and published at noon on 1 January
+    /// 2021, with the other fields empty
+    pub fn test(server_path: &str) -> Page {
+        let date = chrono::NaiveDate::from_ymd_opt(2021, 1, 1)
+            .and_then(|date| date.and_hms_opt(12, 0, 0))
+            .unwrap();
+        Page {
+            id: PageId::new(),
+            published: date,
+            modified: date,
+            modified_on_disk: date,
+            local_path: "".into(),
+            server_path: server_path.into(),
+            title: "".into(),
+            html: "".into(),
+            hash: "".into(),
+            noindex: false,
+            toc: "".into(),
+            word_count: 0,
+            reading_time: 0,
+        }
+    }
 }
 
 // impl Article {
@@ -47,13 +138,14 @@ pub struct Page {
 //     }
 // }
 
-// #[derive(Debug, Queryable, Identifiable, Insertable, AsChangeset)]
-// pub struct Resource {
-//     pub id: ResourceId,
-//     pub modified_on_disk: NaiveDateTime,
-//     pub local_path: String,
-//     pub server_path: String,
-// }
+#[derive(Debug, Queryable, Identifiable, Insertable, AsChangeset)]
+pub struct Resource {
+    pub id: ResourceId,
+    pub hash: String,
+    pub modified_on_disk: NaiveDateTime,
+    pub local_path: String,
+    pub server_path: String,
+}
 
 #[derive(Debug, Queryable, Identifiable, Insertable, AsChangeset)]
 pub struct Image {
@@ -62,9 +154,28 @@ pub struct Image {
     pub width: i32,
     pub height: i32,
     pub local_path: String,
+}
+
+/// Category or tag of the articles
+#[derive(Debug, Clone, Queryable, Identifiable, Insertable, AsChangeset)]
+pub struct Term {
+    pub id: TermId,
+    pub kind: String,
+    pub name: String,
     pub server_path: String,
 }
 
+impl Term {
+    pub const CATEGORY: &'static str = "category";
+    pub const TAG: &'static str = "tag";
+}
+
+#[derive(Debug, Queryable, Insertable)]
+pub struct ArticleTerm {
+    pub article_id: ArticleId,
+    pub term_id: TermId,
+}
+
 #[derive(Debug, Queryable, Identifiable, Insertable, AsChangeset)]
 pub struct Url {
     pub id: UrlId,
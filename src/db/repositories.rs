@@ -1,26 +1,109 @@
 use async_std::path::PathBuf;
-use chrono::Duration;
+use chrono::{Duration, NaiveDateTime};
 use diesel::{r2d2::ConnectionManager, SqliteConnection};
 use r2d2::Pool;
 
-use super::{models::Article, DbConnection};
-use super::{schema::articles::dsl::*, DbResult};
+use super::schema::{article_terms, articles, images, pages, resources, terms};
+use super::{
+    models::{Article, ArticleTerm, Image, Page, Resource, Term},
+    DbConnection,
+};
+use super::{DbResult, Error, Published};
 use diesel::prelude::*;
+use diesel::sqlite::Sqlite;
+
+impl Published {
+    fn filter<'a>(
+        &self,
+        query: articles::BoxedQuery<'a, Sqlite>,
+    ) -> articles::BoxedQuery<'a, Sqlite> {
+        let query = query.filter(articles::published.le(self.until));
+        if self.drafts {
+            query
+        } else {
+            query.filter(articles::draft.eq(false))
+        }
+    }
+}
 
 impl Article {
     pub async fn get_all(dbc: &DbConnection) -> DbResult<Vec<Article>> {
-        let foo = articles.limit(5).load::<Article>(&dbc.get()?);
-        Ok(foo.expect("Error loading posts"))
+        Ok(articles::table.load::<Article>(&dbc.get()?)?)
+    }
+
+    /// Published articles, the most recently published first
+    pub async fn get_newest(dbc: &DbConnection, published: Published) -> DbResult<Vec<Article>> {
+        Ok(published
+            .filter(articles::table.into_boxed())
+            .order(articles::published.desc())
+            .load::<Article>(&dbc.get()?)?)
+    }
+
+    /// Page of the published articles, the most recently published first
+    pub async fn get_page(
+        dbc: &DbConnection,
+        published: Published,
+        offset: i64,
+        limit: i64,
+    ) -> DbResult<Vec<Article>> {
+        Ok(published
+            .filter(articles::table.into_boxed())
+            .order((articles::published.desc(), articles::server_path))
+            .offset(offset)
+            .limit(limit)
+            .load::<Article>(&dbc.get()?)?)
+    }
+
+    /// Articles to be published after `published.until`, the next one first
+    pub async fn get_scheduled(dbc: &DbConnection, published: Published) -> DbResult<Vec<Article>> {
+        let mut query = articles::table
+            .filter(articles::published.gt(published.until))
+            .into_boxed();
+        if !published.drafts {
+            query = query.filter(articles::draft.eq(false));
+        }
+        Ok(query
+            .order(articles::published)
+            .load::<Article>(&dbc.get()?)?)
     }
 
+    pub async fn count(dbc: &DbConnection, published: Published) -> DbResult<i64> {
+        Ok(published
+            .filter(articles::table.into_boxed())
+            .count()
+            .get_result::<i64>(&dbc.get()?)?)
+    }
+
+    pub async fn get_by_local_path(dbc: &DbConnection, path: &str) -> DbResult<Option<Article>> {
+        Ok(articles::table
+            .filter(articles::local_path.eq(path))
+            .first::<Article>(&dbc.get()?)
+            .optional()?)
+    }
+
+    /// Saves the article, unless other article has the same server path
     pub async fn save(&self, dbc: &DbConnection) -> DbResult<()> {
-        // SQLite and MySQL
-        diesel::replace_into(articles)
-            .values(self)
-            .execute(&dbc.get()?)
-            .unwrap();
+        let conn = dbc.get()?;
+        conn.immediate_transaction(|| {
+            let colliding = articles::table
+                .filter(articles::server_path.eq(&self.server_path))
+                .filter(articles::local_path.ne(&self.local_path))
+                .select(articles::local_path)
+                .first::<String>(&conn)
+                .optional()?;
+            if let Some(local_path) = colliding {
+                return Err(Error::ServerPathCollision {
+                    server_path: self.server_path.clone(),
+                    local_path,
+                });
+            }
 
-        Ok(())
+            // SQLite and MySQL
+            diesel::replace_into(articles::table)
+                .values(self)
+                .execute(&conn)?;
+            Ok(())
+        })
         // PG (and upcoming 1.4.6 Diesel release):
         // diesel::insert_into(articles)
         //     .values(self)
@@ -30,9 +113,21 @@ impl Article {
         //     .execute(connection);
     }
 
+    /// Stores the modification time of the unchanged article
+    pub async fn set_modified_on_disk(
+        &self,
+        dbc: &DbConnection,
+        modified_on_disk: &NaiveDateTime,
+    ) -> DbResult<()> {
+        diesel::update(articles::table.filter(articles::id.eq(&self.id)))
+            .set(articles::modified_on_disk.eq(modified_on_disk))
+            .execute(&dbc.get()?)?;
+        Ok(())
+    }
+
     pub async fn delete(&self, dbc: &DbConnection) -> DbResult<()> {
-        diesel::delete(articles)
-            .filter(id.eq(&self.id))
+        diesel::delete(articles::table)
+            .filter(articles::id.eq(&self.id))
             .execute(&dbc.get()?)
             .unwrap();
 
@@ -48,49 +143,239 @@ impl Article {
             .map(|s| s.to_string_lossy())
             .collect::<Vec<_>>();
 
-        Ok(diesel::delete(articles)
-            .filter(local_path.ne_all(local_paths))
+        Ok(diesel::delete(articles::table)
+            .filter(articles::local_path.ne_all(local_paths))
+            .execute(&dbc.get()?)?)
+    }
+
+    /// Replaces the terms of the article, terms with the same server path are
+    /// shared between the articles
+    pub async fn set_terms(&self, dbc: &DbConnection, new_terms: &[Term]) -> DbResult<()> {
+        let conn = dbc.get()?;
+        conn.immediate_transaction(|| {
+            diesel::delete(article_terms::table)
+                .filter(article_terms::article_id.eq(&self.id))
+                .execute(&conn)?;
+
+            for term in new_terms {
+                let existing = terms::table
+                    .filter(terms::server_path.eq(&term.server_path))
+                    .first::<Term>(&conn)
+                    .optional()?;
+                let term_id = match existing {
+                    Some(existing) => existing.id,
+                    None => {
+                        diesel::insert_into(terms::table)
+                            .values(term)
+                            .execute(&conn)?;
+                        term.id.clone()
+                    }
+                };
+                diesel::replace_into(article_terms::table)
+                    .values(&ArticleTerm {
+                        article_id: self.id.clone(),
+                        term_id,
+                    })
+                    .execute(&conn)?;
+            }
+            Ok(())
+        })
+    }
+
+    pub async fn get_terms(&self, dbc: &DbConnection) -> DbResult<Vec<Term>> {
+        Ok(terms::table
+            .inner_join(article_terms::table.on(article_terms::term_id.eq(terms::id)))
+            .filter(article_terms::article_id.eq(&self.id))
+            .select(terms::all_columns)
+            .order((terms::kind, terms::name))
+            .load::<Term>(&dbc.get()?)?)
+    }
+}
+
+impl Page {
+    pub async fn get_all(dbc: &DbConnection) -> DbResult<Vec<Page>> {
+        Ok(pages::table.load::<Page>(&dbc.get()?)?)
+    }
+
+    pub async fn get_by_local_path(dbc: &DbConnection, path: &str) -> DbResult<Option<Page>> {
+        Ok(pages::table
+            .filter(pages::local_path.eq(path))
+            .first::<Page>(&dbc.get()?)
+            .optional()?)
+    }
+
+    /// Saves the page, unless other page has the same server path
+    pub async fn save(&self, dbc: &DbConnection) -> DbResult<()> {
+        let conn = dbc.get()?;
+        conn.immediate_transaction(|| {
+            let colliding = pages::table
+                .filter(pages::server_path.eq(&self.server_path))
+                .filter(pages::local_path.ne(&self.local_path))
+                .select(pages::local_path)
+                .first::<String>(&conn)
+                .optional()?;
+            if let Some(local_path) = colliding {
+                return Err(Error::ServerPathCollision {
+                    server_path: self.server_path.clone(),
+                    local_path,
+                });
+            }
+
+            diesel::replace_into(pages::table)
+                .values(self)
+                .execute(&conn)?;
+            Ok(())
+        })
+    }
+
+    /// Stores the modification time of the unchanged page
+    pub async fn set_modified_on_disk(
+        &self,
+        dbc: &DbConnection,
+        modified_on_disk: &NaiveDateTime,
+    ) -> DbResult<()> {
+        diesel::update(pages::table.filter(pages::id.eq(&self.id)))
+            .set(pages::modified_on_disk.eq(modified_on_disk))
+            .execute(&dbc.get()?)?;
+        Ok(())
+    }
+
+    pub async fn delete(&self, dbc: &DbConnection) -> DbResult<()> {
+        diesel::delete(pages::table)
+            .filter(pages::id.eq(&self.id))
+            .execute(&dbc.get()?)?;
+        Ok(())
+    }
+
+    pub async fn clean_non_existing(
+        dbc: &DbConnection,
+        existing_page_files: &[PathBuf],
+    ) -> DbResult<usize> {
+        let local_paths = existing_page_files
+            .iter()
+            .map(|s| s.to_string_lossy())
+            .collect::<Vec<_>>();
+
+        Ok(diesel::delete(pages::table)
+            .filter(pages::local_path.ne_all(local_paths))
+            .execute(&dbc.get()?)?)
+    }
+}
+
+impl Image {
+    pub async fn get_by_local_path(dbc: &DbConnection, path: &str) -> DbResult<Option<Image>> {
+        Ok(images::table
+            .filter(images::local_path.eq(path))
+            .first::<Image>(&dbc.get()?)
+            .optional()?)
+    }
+
+    pub async fn save(&self, dbc: &DbConnection) -> DbResult<()> {
+        diesel::replace_into(images::table)
+            .values(self)
+            .execute(&dbc.get()?)?;
+        Ok(())
+    }
+}
+
+impl Term {
+    pub async fn get_all(dbc: &DbConnection) -> DbResult<Vec<Term>> {
+        Ok(terms::table
+            .order((terms::kind, terms::name))
+            .load::<Term>(&dbc.get()?)?)
+    }
+
+    /// Published articles of the term, the most recently published first
+    pub async fn get_articles(
+        &self,
+        dbc: &DbConnection,
+        published: Published,
+    ) -> DbResult<Vec<Article>> {
+        let article_ids = article_terms::table
+            .filter(article_terms::term_id.eq(&self.id))
+            .select(article_terms::article_id);
+        Ok(published
+            .filter(articles::table.into_boxed())
+            .filter(articles::id.eq_any(article_ids))
+            .order(articles::published.desc())
+            .load::<Article>(&dbc.get()?)?)
+    }
+
+    /// Removes the terms no longer used by any article, and returns them
+    ///
+    /// Articles are replaced on save, so the links of the previous rows are
+    /// removed first.
+    pub async fn clean_unused(dbc: &DbConnection) -> DbResult<Vec<Term>> {
+        let conn = dbc.get()?;
+        conn.immediate_transaction(|| {
+            diesel::delete(article_terms::table)
+                .filter(diesel::dsl::not(
+                    article_terms::article_id.eq_any(articles::table.select(articles::id)),
+                ))
+                .execute(&conn)?;
+
+            let unused = terms::table
+                .filter(diesel::dsl::not(
+                    terms::id.eq_any(article_terms::table.select(article_terms::term_id)),
+                ))
+                .load::<Term>(&conn)?;
+            diesel::delete(terms::table)
+                .filter(terms::id.eq_any(unused.iter().map(|term| &term.id)))
+                .execute(&conn)?;
+            Ok(unused)
+        })
+    }
+}
+
+impl Resource {
+    pub async fn get_all(dbc: &DbConnection) -> DbResult<Vec<Resource>> {
+        Ok(resources::table.load::<Resource>(&dbc.get()?)?)
+    }
+
+    pub async fn get_by_local_path(dbc: &DbConnection, path: &str) -> DbResult<Option<Resource>> {
+        Ok(resources::table
+            .filter(resources::local_path.eq(path))
+            .first::<Resource>(&dbc.get()?)
+            .optional()?)
+    }
+
+    pub async fn save(&self, dbc: &DbConnection) -> DbResult<()> {
+        diesel::replace_into(resources::table)
+            .values(self)
+            .execute(&dbc.get()?)?;
+        Ok(())
+    }
+
+    /// Removes the resources of other local paths than the used ones
+    pub async fn clean_unused(dbc: &DbConnection, used_local_paths: &[String]) -> DbResult<usize> {
+        Ok(diesel::delete(resources::table)
+            .filter(resources::local_path.ne_all(used_local_paths))
             .execute(&dbc.get()?)?)
     }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::db::DbConnection;
+    use crate::db::{DbConnection, Error};
 
-    use super::super::ArticleId;
-    use super::Article;
+    use super::super::{ImageId, Published, ResourceId, TermId};
+    use super::{Article, Image, Page, Resource, Term};
 
     async fn create_test_articles(dbc: &DbConnection) {
         let test1 = Article {
-            html: "".into(),
-            id: ArticleId::new(),
             local_path: "./examples/post01.md".into(),
-            modified: chrono::Local::now().naive_utc(),
-            modified_on_disk: chrono::Local::now().naive_utc(),
-            published: chrono::Local::now().naive_utc(),
-            server_path: "/examples/post01/".into(),
             title: "Example post 01".into(),
+            ..Article::test("/examples/post01/")
         };
         let test2 = Article {
-            html: "".into(),
-            id: ArticleId::new(),
             local_path: "./examples/post02.md".into(),
-            modified: chrono::Local::now().naive_utc(),
-            modified_on_disk: chrono::Local::now().naive_utc(),
-            published: chrono::Local::now().naive_utc(),
-            server_path: "/examples/post02/".into(),
             title: "Example post 02".into(),
+            ..Article::test("/examples/post02/")
         };
         let test3 = Article {
-            html: "".into(),
-            id: ArticleId::new(),
             local_path: "./examples/non-existing.md".into(),
-            modified: chrono::Local::now().naive_utc(),
-            modified_on_disk: chrono::Local::now().naive_utc(),
-            published: chrono::Local::now().naive_utc(),
-            server_path: "/examples/non-existing/".into(),
             title: "Example non existing".into(),
+            ..Article::test("/examples/non-existing/")
         };
 
         let _ = test1.save(&dbc).await;
@@ -114,4 +399,226 @@ mod test {
 
         assert_eq!(Article::get_all(&dbc).await.unwrap().len(), 2);
     }
+
+    #[async_std::test]
+    async fn test_set_modified_on_disk() {
+        let dbc = DbConnection::new_from_url(":memory:").await.unwrap();
+        create_test_articles(&dbc).await;
+
+        let article = Article::get_by_local_path(&dbc, "./examples/post01.md")
+            .await
+            .unwrap()
+            .unwrap();
+        let touched = chrono::NaiveDate::from_ymd_opt(2021, 1, 1)
+            .and_then(|date| date.and_hms_opt(12, 0, 0))
+            .unwrap();
+        article.set_modified_on_disk(&dbc, &touched).await.unwrap();
+
+        let article = Article::get_by_local_path(&dbc, "./examples/post01.md")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(article.modified_on_disk, touched);
+    }
+
+    #[async_std::test]
+    async fn test_get_page() {
+        let dbc = DbConnection::new_from_url(":memory:").await.unwrap();
+        for day in 1..=3 {
+            let published = chrono::NaiveDate::from_ymd_opt(2021, 1, day)
+                .and_then(|date| date.and_hms_opt(12, 0, 0))
+                .unwrap();
+            let article = Article {
+                local_path: format!("./examples/post0{}.md", day),
+                published,
+                modified: published,
+                modified_on_disk: published,
+                draft: day == 2,
+                title: format!("Example post 0{}", day),
+                ..Article::test(&format!("/examples/post0{}/", day))
+            };
+            article.save(&dbc).await.unwrap();
+        }
+
+        let titles = |articles: Vec<Article>| -> Vec<String> {
+            articles.into_iter().map(|article| article.title).collect()
+        };
+        let now = chrono::Local::now().naive_utc();
+        let published = Published {
+            drafts: true,
+            until: now,
+        };
+        assert_eq!(Article::count(&dbc, published).await.unwrap(), 3);
+        assert_eq!(
+            titles(Article::get_page(&dbc, published, 0, 2).await.unwrap()),
+            vec!["Example post 03", "Example post 02"]
+        );
+        assert_eq!(
+            titles(Article::get_page(&dbc, published, 2, 2).await.unwrap()),
+            vec!["Example post 01"]
+        );
+
+        // Post 02 is a draft
+        let published = Published {
+            drafts: false,
+            until: now,
+        };
+        assert_eq!(Article::count(&dbc, published).await.unwrap(), 2);
+        assert_eq!(
+            titles(Article::get_page(&dbc, published, 0, 2).await.unwrap()),
+            vec!["Example post 03", "Example post 01"]
+        );
+
+        // Post 03 is scheduled
+        let published = Published {
+            drafts: true,
+            until: chrono::NaiveDate::from_ymd_opt(2021, 1, 2)
+                .and_then(|date| date.and_hms_opt(12, 0, 0))
+                .unwrap(),
+        };
+        assert_eq!(Article::count(&dbc, published).await.unwrap(), 2);
+        assert_eq!(
+            titles(Article::get_scheduled(&dbc, published).await.unwrap()),
+            vec!["Example post 03"]
+        );
+    }
+
+    #[async_std::test]
+    async fn test_terms() {
+        let dbc = DbConnection::new_from_url(":memory:").await.unwrap();
+        create_test_articles(&dbc).await;
+        let term = |name: &str| Term {
+            id: TermId::new(),
+            kind: Term::TAG.into(),
+            name: name.into(),
+            server_path: format!("/tags/{}", name.to_lowercase()),
+        };
+
+        let articles = Article::get_all(&dbc).await.unwrap();
+        articles[0]
+            .set_terms(&dbc, &[term("Rust"), term("Diesel")])
+            .await
+            .unwrap();
+        articles[1].set_terms(&dbc, &[term("rust")]).await.unwrap();
+
+        let terms = Term::get_all(&dbc).await.unwrap();
+        assert_eq!(terms.len(), 2);
+        let rust = terms.iter().find(|term| term.name == "Rust").unwrap();
+        let published = Published {
+            drafts: true,
+            until: chrono::Local::now().naive_utc(),
+        };
+        assert_eq!(rust.get_articles(&dbc, published).await.unwrap().len(), 2);
+        assert_eq!(articles[1].get_terms(&dbc).await.unwrap()[0].name, "Rust");
+
+        articles[0].delete(&dbc).await.unwrap();
+        let unused = Term::clean_unused(&dbc).await.unwrap();
+        assert_eq!(unused.len(), 1);
+        assert_eq!(unused[0].name, "Diesel");
+        assert_eq!(rust.get_articles(&dbc, published).await.unwrap().len(), 1);
+    }
+
+    #[async_std::test]
+    async fn test_clean_non_existing_pages() {
+        let dbc = DbConnection::new_from_url(":memory:").await.unwrap();
+        for name in &["example", "non-existing"] {
+            let page = Page {
+                local_path: format!("./examples/pages/{}.md", name),
+                title: name.to_string(),
+                ..Page::test(&format!("/{}", name))
+            };
+            page.save(&dbc).await.unwrap();
+        }
+
+        assert_eq!(Page::get_all(&dbc).await.unwrap().len(), 2);
+
+        Page::clean_non_existing(&dbc, &["./examples/pages/example.md".into()])
+            .await
+            .unwrap();
+
+        let pages = Page::get_all(&dbc).await.unwrap();
+        assert_eq!(pages.len(), 1);
+        assert_eq!(pages[0].server_path, "/example");
+    }
+
+    #[async_std::test]
+    async fn test_image_by_local_path() {
+        let dbc = DbConnection::new_from_url(":memory:").await.unwrap();
+        let image = Image {
+            id: ImageId::new(),
+            modified_on_disk: chrono::Local::now().naive_utc(),
+            width: 1600,
+            height: 900,
+            local_path: "./examples/articles/res01.png".into(),
+        };
+        image.save(&dbc).await.unwrap();
+
+        let found = Image::get_by_local_path(&dbc, "./examples/articles/res01.png")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(found.id, image.id);
+        assert_eq!((found.width, found.height), (1600, 900));
+
+        assert!(
+            Image::get_by_local_path(&dbc, "./examples/articles/none.png")
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[async_std::test]
+    async fn test_server_path_collision() {
+        let dbc = DbConnection::new_from_url(":memory:").await.unwrap();
+        let article = |local_path: &str| Article {
+            local_path: local_path.into(),
+            title: "Foo".into(),
+            ..Article::test("/articles/foo")
+        };
+
+        article("./examples/2020/foo.md").save(&dbc).await.unwrap();
+
+        // Saving the same file again replaces it
+        article("./examples/2020/foo.md").save(&dbc).await.unwrap();
+
+        match article("./examples/2021/foo.md").save(&dbc).await {
+            Err(Error::ServerPathCollision {
+                server_path,
+                local_path,
+            }) => {
+                assert_eq!(server_path, "/articles/foo");
+                assert_eq!(local_path, "./examples/2020/foo.md");
+            }
+            other => panic!("expected collision, got {:?}", other),
+        }
+        assert_eq!(Article::get_all(&dbc).await.unwrap().len(), 1);
+    }
+
+    #[async_std::test]
+    async fn test_shared_resource_server_path() {
+        let dbc = DbConnection::new_from_url(":memory:").await.unwrap();
+        for dir in &["post01", "post02", "post03"] {
+            let resource = Resource {
+                id: ResourceId::new(),
+                hash: "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855".into(),
+                modified_on_disk: chrono::Local::now().naive_utc(),
+                local_path: format!("./examples/{}/res01.svg", dir),
+                server_path: "/resources/res01.e3b0c442.svg".into(),
+            };
+            resource.save(&dbc).await.unwrap();
+        }
+        assert_eq!(Resource::get_all(&dbc).await.unwrap().len(), 3);
+
+        Resource::clean_unused(
+            &dbc,
+            &[
+                "./examples/post01/res01.svg".into(),
+                "./examples/post02/res01.svg".into(),
+            ],
+        )
+        .await
+        .unwrap();
+        assert_eq!(Resource::get_all(&dbc).await.unwrap().len(), 2);
+    }
 }
@@ -1,3 +1,10 @@
+table! {
+    article_terms (article_id, term_id) {
+        article_id -> Text,
+        term_id -> Text,
+    }
+}
+
 table! {
     articles (id) {
         id -> Text,
@@ -8,6 +15,15 @@ table! {
         server_path -> Text,
         title -> Text,
         html -> Text,
+        hash -> Text,
+        noindex -> Bool,
+        draft -> Bool,
+        old_url -> Nullable<Text>,
+        toc -> Text,
+        excerpt -> Text,
+        description -> Text,
+        word_count -> Integer,
+        reading_time -> Integer,
     }
 }
 
@@ -18,7 +34,6 @@ table! {
         width -> Integer,
         height -> Integer,
         local_path -> Text,
-        server_path -> Text,
     }
 }
 
@@ -32,6 +47,30 @@ table! {
         server_path -> Text,
         title -> Text,
         html -> Text,
+        hash -> Text,
+        noindex -> Bool,
+        toc -> Text,
+        word_count -> Integer,
+        reading_time -> Integer,
+    }
+}
+
+table! {
+    resources (id) {
+        id -> Text,
+        hash -> Text,
+        modified_on_disk -> Timestamp,
+        local_path -> Text,
+        server_path -> Text,
+    }
+}
+
+table! {
+    terms (id) {
+        id -> Text,
+        kind -> Text,
+        name -> Text,
+        server_path -> Text,
     }
 }
 
@@ -43,8 +82,11 @@ table! {
 }
 
 allow_tables_to_appear_in_same_query!(
+    article_terms,
     articles,
     images,
     pages,
+    resources,
+    terms,
     urls,
 );
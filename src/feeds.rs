@@ -0,0 +1,317 @@
+use chrono::{NaiveDateTime, SecondsFormat, TimeZone, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::config::SiteConfig;
+use crate::db::models::{Article, Term};
+
+/// Server path of the Atom feed
+pub const ATOM_PATH: &str = "/atom.xml";
+
+/// Server path of the JSON feed
+pub const JSON_FEED_PATH: &str = "/feed.json";
+
+/// Absolute urls of the local files by their file:// url, feed readers can't
+/// resolve links relative to the site
+pub fn absolute_urls(
+    server_paths: &HashMap<String, String>,
+    site: &SiteConfig,
+) -> HashMap<String, String> {
+    server_paths
+        .iter()
+        .map(|(file_url, server_path)| (file_url.clone(), site.url(server_path).into()))
+        .collect()
+}
+
+pub fn rfc3339(date: &NaiveDateTime) -> String {
+    Utc.from_utc_datetime(date)
+        .to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+pub fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Atom feeds must have an author, the site stands in for it when the author
+/// is not configured
+fn atom_author(site: &SiteConfig) -> &str {
+    if !site.author.is_empty() {
+        &site.author
+    } else if !site.site_name.is_empty() {
+        &site.site_name
+    } else {
+        site.base_url.as_str()
+    }
+}
+
+/// Atom feed of the articles and their terms, the html of the articles must
+/// have absolute urls
+pub fn atom_feed(articles: &[(Article, Vec<Term>)], site: &SiteConfig) -> String {
+    let updated = articles
+        .iter()
+        .map(|(article, _)| article.modified)
+        .max()
+        .unwrap_or_else(|| Utc::now().naive_utc());
+
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    xml.push_str(&format!(
+        "<id>{}</id>\n",
+        escape_xml(site.base_url.as_str())
+    ));
+    xml.push_str(&format!("<title>{}</title>\n", escape_xml(&site.site_name)));
+    xml.push_str(&format!("<updated>{}</updated>\n", rfc3339(&updated)));
+    xml.push_str(&format!(
+        "<link rel=\"self\" href=\"{}\"/>\n",
+        escape_xml(site.url(ATOM_PATH).as_str())
+    ));
+    xml.push_str(&format!(
+        "<link rel=\"alternate\" href=\"{}\"/>\n",
+        escape_xml(site.base_url.as_str())
+    ));
+    xml.push_str(&format!(
+        "<author><name>{}</name></author>\n",
+        escape_xml(atom_author(site))
+    ));
+
+    for (article, terms) in articles {
+        let url = site.url(&article.server_path);
+        xml.push_str("<entry>\n");
+        xml.push_str(&format!("<id>{}</id>\n", escape_xml(url.as_str())));
+        xml.push_str(&format!("<title>{}</title>\n", escape_xml(&article.title)));
+        xml.push_str(&format!(
+            "<link rel=\"alternate\" href=\"{}\"/>\n",
+            escape_xml(url.as_str())
+        ));
+        xml.push_str(&format!(
+            "<published>{}</published>\n",
+            rfc3339(&article.published)
+        ));
+        xml.push_str(&format!(
+            "<updated>{}</updated>\n",
+            rfc3339(&article.modified)
+        ));
+        if !article.description.is_empty() {
+            xml.push_str(&format!(
+                "<summary>{}</summary>\n",
+                escape_xml(&article.description)
+            ));
+        }
+        for term in terms {
+            xml.push_str(&format!(
+                "<category term=\"{}\"/>\n",
+                escape_xml(&term.name)
+            ));
+        }
+        xml.push_str(&format!(
+            "<content type=\"html\">{}</content>\n",
+            escape_xml(&article.html)
+        ));
+        xml.push_str("</entry>\n");
+    }
+
+    xml.push_str("</feed>\n");
+    xml
+}
+
+/// JSON Feed 1.1, see https://jsonfeed.org/version/1.1
+#[derive(Debug, Serialize)]
+struct JsonFeed {
+    version: &'static str,
+    title: String,
+    home_page_url: String,
+    feed_url: String,
+    language: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    authors: Vec<JsonFeedAuthor>,
+    items: Vec<JsonFeedItem>,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonFeedAuthor {
+    name: String,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonFeedItem {
+    id: String,
+    url: String,
+    title: String,
+    content_html: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    summary: String,
+    date_published: String,
+    date_modified: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+
+    /// Extensions of the feed are named with an underscore
+    #[serde(rename = "_reading")]
+    reading: JsonFeedReading,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonFeedReading {
+    word_count: i32,
+
+    /// Estimated reading time in minutes
+    minutes: i32,
+}
+
+/// JSON feed of the articles and their terms, the html of the articles must
+/// have absolute urls
+pub fn json_feed(
+    articles: &[(Article, Vec<Term>)],
+    site: &SiteConfig,
+) -> Result<String, serde_json::Error> {
+    let feed = JsonFeed {
+        version: "https://jsonfeed.org/version/1.1",
+        title: site.site_name.clone(),
+        home_page_url: site.base_url.to_string(),
+        feed_url: site.url(JSON_FEED_PATH).into(),
+
+        // Locales are written with an underscore, e.g. `en_US`
+        language: site.locale.replace('_', "-"),
+        authors: if site.author.is_empty() {
+            vec![]
+        } else {
+            vec![JsonFeedAuthor {
+                name: site.author.clone(),
+            }]
+        },
+        items: articles
+            .iter()
+            .map(|(article, terms)| {
+                let url: String = site.url(&article.server_path).into();
+                JsonFeedItem {
+                    id: url.clone(),
+                    url,
+                    title: article.title.clone(),
+                    content_html: article.html.clone(),
+                    summary: article.description.clone(),
+                    date_published: rfc3339(&article.published),
+                    date_modified: rfc3339(&article.modified),
+                    tags: terms.iter().map(|term| term.name.clone()).collect(),
+                    reading: JsonFeedReading {
+                        word_count: article.word_count,
+                        minutes: article.reading_time,
+                    },
+                }
+            })
+            .collect(),
+    };
+    serde_json::to_string_pretty(&feed)
+}
+
+#[cfg(test)]
+mod test_feeds {
+    use super::{atom_feed, escape_xml, json_feed};
+    use crate::config::SiteConfig;
+    use crate::db::{
+        models::{Article, Term},
+        TermId,
+    };
+    use chrono::NaiveDate;
+
+    fn article(title: &str, server_path: &str, day: u32) -> (Article, Vec<Term>) {
+        let date = NaiveDate::from_ymd_opt(2021, 1, day)
+            .and_then(|date| date.and_hms_opt(12, 0, 0))
+            .unwrap();
+        let article = Article {
+            published: date,
+            modified: date,
+            modified_on_disk: date,
+            title: title.into(),
+            html: "<p><a href=\"https://example.com/\">Link</a></p>".into(),
+            description: format!("About {}", title),
+            word_count: 300,
+            reading_time: 2,
+            ..Article::test(server_path)
+        };
+        let terms = vec![Term {
+            id: TermId::new(),
+            kind: Term::TAG.into(),
+            name: "Rust & Diesel".into(),
+            server_path: "/tags/rust-diesel".into(),
+        }];
+        (article, terms)
+    }
+
+    #[test]
+    fn test_escape_xml() {
+        assert_eq!(
+            escape_xml("<a href=\"x\">Tom & Jerry's</a>"),
+            "&lt;a href=&quot;x&quot;&gt;Tom &amp; Jerry&apos;s&lt;/a&gt;"
+        );
+    }
+
+    #[test]
+    fn test_atom_feed() {
+        let feed = atom_feed(
+            &[
+                article("Second & last", "/articles/post02", 2),
+                article("First", "/articles/post01", 1),
+            ],
+            &SiteConfig::default(),
+        );
+
+        assert!(feed.contains("<updated>2021-01-02T12:00:00Z</updated>"));
+        assert!(feed.contains("<title>Second &amp; last</title>"));
+        assert!(feed.contains("<summary>About Second &amp; last</summary>"));
+        assert!(feed.contains("<category term=\"Rust &amp; Diesel\"/>"));
+        assert!(feed.contains("<id>http://127.0.0.1:8080/articles/post01</id>"));
+        assert!(feed.contains(
+            "<content type=\"html\">&lt;p&gt;&lt;a href=&quot;https://example.com/&quot;&gt;"
+        ));
+        assert!(feed.find("post02").unwrap() < feed.find("post01").unwrap());
+    }
+
+    #[test]
+    fn test_atom_feed_author() {
+        let mut site = SiteConfig::default();
+        assert!(
+            atom_feed(&[], &site).contains("<author><name>http://127.0.0.1:8080/</name></author>")
+        );
+
+        site.site_name = "Example & Co".into();
+        assert!(atom_feed(&[], &site).contains("<author><name>Example &amp; Co</name></author>"));
+
+        site.author = "Tom & Jerry".into();
+        assert!(atom_feed(&[], &site).contains("<author><name>Tom &amp; Jerry</name></author>"));
+    }
+
+    #[test]
+    fn test_json_feed() {
+        let feed = json_feed(
+            &[article("First", "/articles/post01", 1)],
+            &SiteConfig::default(),
+        )
+        .unwrap();
+        let feed: serde_json::Value = serde_json::from_str(&feed).unwrap();
+
+        assert_eq!(feed["version"], "https://jsonfeed.org/version/1.1");
+        assert_eq!(feed["feed_url"], "http://127.0.0.1:8080/feed.json");
+        assert_eq!(feed["language"], "en-US");
+        assert_eq!(
+            feed["items"][0]["url"],
+            "http://127.0.0.1:8080/articles/post01"
+        );
+        assert_eq!(feed["items"][0]["date_published"], "2021-01-01T12:00:00Z");
+        assert_eq!(feed["items"][0]["summary"], "About First");
+        assert_eq!(feed["items"][0]["tags"][0], "Rust & Diesel");
+        assert_eq!(feed["items"][0]["_reading"]["minutes"], 2);
+        assert!(feed.get("authors").is_none());
+    }
+}
@@ -0,0 +1,56 @@
+use async_std::fs;
+use async_std::path::PathBuf;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use sha2::{Digest, Sha256};
+
+/// SHA-256 of the contents as a hex string
+pub fn content_hash(contents: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(contents))
+}
+
+/// Version of the compiled documents, bump it when the compiled output or the
+/// columns derived from the markdown change so that stored documents are
+/// compiled again
+pub const COMPILER_VERSION: u32 = 2;
+
+/// Content hash of a markdown document, prefixed with the compiler version
+pub fn document_hash(contents: &[u8]) -> String {
+    format!("{}-{}", COMPILER_VERSION, content_hash(contents))
+}
+
+/// Was the stored document hash made by the current compiler
+pub fn is_current_version(hash: &str) -> bool {
+    hash.starts_with(&format!("{}-", COMPILER_VERSION))
+}
+
+pub async fn file_hash(file: &PathBuf) -> Result<String, std::io::Error> {
+    Ok(content_hash(&fs::read(file).await?))
+}
+
+/// Modification time of the file, as stored in the database
+pub async fn modified_on_disk(file: &PathBuf) -> Result<NaiveDateTime, std::io::Error> {
+    let modified: DateTime<Utc> = fs::metadata(file).await?.modified()?.into();
+    Ok(modified.naive_utc())
+}
+
+#[cfg(test)]
+mod test_fingerprint {
+    use super::{content_hash, document_hash, is_current_version};
+
+    #[test]
+    fn test_content_hash() {
+        assert_eq!(
+            content_hash(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_ne!(content_hash(b"post01"), content_hash(b"post02"));
+    }
+
+    #[test]
+    fn test_document_hash() {
+        assert!(document_hash(b"post01").ends_with(&content_hash(b"post01")));
+        assert!(is_current_version(&document_hash(b"post01")));
+        assert!(!is_current_version(&content_hash(b"post01")));
+        assert!(!is_current_version(""));
+    }
+}
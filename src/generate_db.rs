@@ -1,37 +1,233 @@
-use crate::{db::models::Article, Message};
+use crate::feeds::{ATOM_PATH, JSON_FEED_PATH};
+use crate::fingerprint::{document_hash, is_current_version, modified_on_disk};
+use crate::generate_html::remove_output;
+use crate::images::{generate_image_db, is_image, set_image_sizes};
+use crate::layout::TERMS_PATH;
+use crate::markdown::SYNTAX_CSS_PATH;
+use crate::redirects::{HTACCESS_PATH, NETLIFY_PATH, NGINX_PATH};
+use crate::resources::{generate_resource_db, is_markdown};
+use crate::search::SEARCH_INDEX_PATH;
+use crate::serve::LIVE_RELOAD_PATH;
+use crate::sitemap::{ROBOTS_PATH, SITEMAP_PATH};
+use crate::slug::slugify;
+use crate::{
+    db::models::Resource,
+    normalize::normalize_sync,
+    urls::{convert_html_urls, file_urls, ConvertedUrls},
+};
+use crate::{
+    db::models::{Article, Page, Term},
+    Message,
+};
 use crate::{db::DbConnection, FilesChange};
 use crate::{
-    db::{ArticleId, PageId},
+    db::{ArticleId, PageId, TermId},
     GenerateParams,
 };
-use crate::{markdown::compile_markdown_file, normalize::normalize};
-use crate::{normalize::normalize_sync, urls::convert_html_urls};
+use crate::{
+    markdown::{compile_markdown_file, excerpt, CompiledMarkdown},
+    normalize::normalize,
+};
 use async_std::channel::Sender;
+use async_std::fs;
 use async_std::path::PathBuf;
 use async_std::task::JoinHandle;
+use chrono::NaiveDateTime;
 use derive_more::From;
 use futures::future::join_all;
 use glob::glob;
 
 #[derive(Debug, From)]
 pub enum Error {
-    // IOError(std::io::Error),
+    IOError(std::io::Error),
     PatternError(glob::PatternError),
     DbError(crate::db::Error),
     CompileMarkdownError(crate::markdown::Error),
     UrlConvertError(crate::urls::Error),
+    ImageError(crate::images::Error),
+    ResourceError(crate::resources::Error),
+    HtmlError(crate::generate_html::Error),
+    JsonError(serde_json::Error),
+    SlugCollision {
+        server_path: String,
+        existing: PathBuf,
+        colliding: PathBuf,
+    },
+    ReservedPath {
+        server_path: String,
+        file: PathBuf,
+    },
     // UrlToFilePath,
 }
 
+/// Saving errors, with server path collisions named by both of the files
+fn save_error(error: crate::db::Error, file: &PathBuf) -> Error {
+    match error {
+        crate::db::Error::ServerPathCollision {
+            server_path,
+            local_path,
+        } => Error::SlugCollision {
+            server_path,
+            existing: local_path.into(),
+            colliding: file.clone(),
+        },
+        error => error.into(),
+    }
+}
+
+/// Is the server path used by the generated outputs, pages are served from the
+/// root so they could replace them
+fn is_reserved_path(server_path: &str) -> bool {
+    let reserved_dirs = ["/articles", "/categories", "/tags", "/page", "/resources"];
+    if reserved_dirs
+        .iter()
+        .any(|dir| server_path == *dir || server_path.starts_with(&format!("{}/", dir)))
+    {
+        return true;
+    }
+
+    let reserved = [
+        TERMS_PATH,
+        ATOM_PATH,
+        JSON_FEED_PATH,
+        SITEMAP_PATH,
+        ROBOTS_PATH,
+        SEARCH_INDEX_PATH,
+        SYNTAX_CSS_PATH,
+        NGINX_PATH,
+        HTACCESS_PATH,
+        NETLIFY_PATH,
+        LIVE_RELOAD_PATH,
+    ];
+    reserved.contains(&server_path)
+}
+
+/// Compiles the markdown file, converts its relative urls and stores the
+/// resources it refers to
+async fn compile_document(
+    file: &PathBuf,
+    root_path: &PathBuf,
+    pool: &DbConnection,
+) -> Result<(CompiledMarkdown, ConvertedUrls), Error> {
+    let markdown = compile_markdown_file(file).await?;
+    let document_path = markdown.local_path.parent().unwrap();
+    let mut converted = convert_html_urls(&markdown.html, &document_path.into(), root_path).await?;
+
+    let mut images = vec![];
+    for url in &converted.urls {
+        if let Ok(path) = url.to_file_path() {
+            let path: PathBuf = path.into();
+            if is_markdown(&path) {
+                continue;
+            }
+            generate_resource_db(&path, &pool).await?;
+            if is_image(&path) {
+                images.push(generate_image_db(&path, &pool).await?);
+            }
+        }
+    }
+    converted.html = set_image_sizes(&converted.html, &images);
+
+    Ok((markdown, converted))
+}
+
+/// Is the stored document still current
+///
+/// The document is current when it was compiled by the current compiler, its
+/// modification time or its content hash is unchanged, and the local
+/// resources it refers to are unchanged.
+async fn is_current(
+    file: &PathBuf,
+    file_modified_on_disk: &NaiveDateTime,
+    stored_modified_on_disk: &NaiveDateTime,
+    stored_hash: &str,
+    stored_html: &str,
+    pool: &DbConnection,
+) -> Result<bool, Error> {
+    if (file_modified_on_disk != stored_modified_on_disk || !is_current_version(stored_hash))
+        && document_hash(&fs::read(file).await?) != stored_hash
+    {
+        return Ok(false);
+    }
+
+    for url in file_urls(stored_html) {
+        if let Ok(path) = url.to_file_path() {
+            let path: PathBuf = path.into();
+            if is_markdown(&path) {
+                continue;
+            }
+            if !path.exists().await {
+                return Ok(false);
+            }
+            match Resource::get_by_local_path(&pool, &path.to_string_lossy()).await? {
+                Some(resource) if resource.modified_on_disk == modified_on_disk(&path).await? => (),
+                _ => return Ok(false),
+            }
+        }
+    }
+
+    Ok(true)
+}
+
+/// Terms of the article, e.g. category `Foo` is served from
+/// `/categories/foo`
+fn article_terms(markdown: &CompiledMarkdown) -> Vec<Term> {
+    let categories = markdown
+        .categories
+        .iter()
+        .map(|name| (Term::CATEGORY, "categories", name));
+    let tags = markdown.tags.iter().map(|name| (Term::TAG, "tags", name));
+
+    categories
+        .chain(tags)
+        .filter_map(|(kind, dir, name)| {
+            let slug = slugify(name);
+            if slug.is_empty() {
+                return None;
+            }
+            Some(Term {
+                id: TermId::new(),
+                kind: kind.into(),
+                name: name.trim().into(),
+                server_path: format!("/{}/{}", dir, slug),
+            })
+        })
+        .collect()
+}
+
+/// Compiles the article to the database, returns `None` if the stored
+/// article is current
 async fn generate_article_db(
     article_file: &PathBuf,
     root_path: &PathBuf,
     pool: &DbConnection,
-) -> Result<Vec<url::Url>, Error> {
-    let markdown = compile_markdown_file(&article_file.into()).await?;
-    let article_path = markdown.local_path.parent().unwrap();
-    let converted =
-        convert_html_urls(&markdown.html, &article_path.into(), &root_path.into()).await?;
+) -> Result<Option<Vec<url::Url>>, Error> {
+    let stored = Article::get_by_local_path(&pool, &article_file.to_string_lossy()).await?;
+    if let Some(article) = stored {
+        let file_modified_on_disk = modified_on_disk(&article_file).await?;
+        if is_current(
+            &article_file,
+            &file_modified_on_disk,
+            &article.modified_on_disk,
+            &article.hash,
+            &article.html,
+            &pool,
+        )
+        .await?
+        {
+            // Touched but unchanged, the next build can skip hashing it
+            if file_modified_on_disk != article.modified_on_disk {
+                article
+                    .set_modified_on_disk(&pool, &file_modified_on_disk)
+                    .await?;
+            }
+            return Ok(None);
+        }
+    }
+
+    let (markdown, converted) = compile_document(&article_file, &root_path, &pool).await?;
+    let terms = article_terms(&markdown);
+    let excerpt_html = excerpt(&converted.html);
 
     let article = Article {
         id: ArticleId::new(),
@@ -41,18 +237,120 @@ async fn generate_article_db(
         published: markdown.published.naive_utc(),
         modified: markdown.modified.naive_utc(),
         modified_on_disk: markdown.modified_on_disk.naive_utc(),
-
-        // TODO: Colliding slugs?
         server_path: format!("/articles/{}", markdown.slug),
+        hash: markdown.hash,
+        noindex: markdown.noindex,
+        draft: markdown.draft,
+        old_url: markdown.old_url.map(String::from),
+        toc: serde_json::to_string(&markdown.toc)?,
+        excerpt: excerpt_html,
+        description: markdown.description,
+        word_count: markdown.word_count as i32,
+        reading_time: markdown.reading_time as i32,
     };
-    article.save(&pool).await?;
+    article
+        .save(&pool)
+        .await
+        .map_err(|err| save_error(err, article_file))?;
+    article.set_terms(&pool, &terms).await?;
 
-    Ok(converted.urls)
+    Ok(Some(converted.urls))
+}
+
+/// Compiles the page to the database, returns `None` if the stored page is
+/// current
+async fn generate_page_db(
+    page_file: &PathBuf,
+    root_path: &PathBuf,
+    pool: &DbConnection,
+) -> Result<Option<Vec<url::Url>>, Error> {
+    let stored = Page::get_by_local_path(&pool, &page_file.to_string_lossy()).await?;
+    if let Some(page) = stored {
+        let file_modified_on_disk = modified_on_disk(&page_file).await?;
+        if is_current(
+            &page_file,
+            &file_modified_on_disk,
+            &page.modified_on_disk,
+            &page.hash,
+            &page.html,
+            &pool,
+        )
+        .await?
+        {
+            // Touched but unchanged, the next build can skip hashing it
+            if file_modified_on_disk != page.modified_on_disk {
+                page.set_modified_on_disk(&pool, &file_modified_on_disk)
+                    .await?;
+            }
+            return Ok(None);
+        }
+    }
+
+    let (markdown, converted) = compile_document(&page_file, &root_path, &pool).await?;
+
+    // Pages are served from the root, e.g. `/example`
+    let server_path = format!("/{}", markdown.slug);
+    if is_reserved_path(&server_path) {
+        return Err(Error::ReservedPath {
+            server_path,
+            file: page_file.clone(),
+        });
+    }
+
+    let page = Page {
+        id: PageId::new(),
+        html: converted.html,
+        title: markdown.title,
+        local_path: markdown.local_path.to_string_lossy().into_owned(),
+        published: markdown.published.naive_utc(),
+        modified: markdown.modified.naive_utc(),
+        modified_on_disk: markdown.modified_on_disk.naive_utc(),
+        server_path,
+        hash: markdown.hash,
+        noindex: markdown.noindex,
+        toc: serde_json::to_string(&markdown.toc)?,
+        word_count: markdown.word_count as i32,
+        reading_time: markdown.reading_time as i32,
+    };
+    page.save(&pool)
+        .await
+        .map_err(|err| save_error(err, page_file))?;
+
+    Ok(Some(converted.urls))
+}
+
+/// Removes the deleted article from the database and the output
+async fn delete_article_db(
+    article_file: &PathBuf,
+    output_dir: &PathBuf,
+    pool: &DbConnection,
+) -> Result<(), Error> {
+    let stored = Article::get_by_local_path(&pool, &article_file.to_string_lossy()).await?;
+    if let Some(article) = stored {
+        remove_output(&output_dir, &article.server_path).await?;
+        article.delete(&pool).await?;
+    }
+    Ok(())
+}
+
+/// Removes the deleted page from the database and the output
+async fn delete_page_db(
+    page_file: &PathBuf,
+    output_dir: &PathBuf,
+    pool: &DbConnection,
+) -> Result<(), Error> {
+    let stored = Page::get_by_local_path(&pool, &page_file.to_string_lossy()).await?;
+    if let Some(page) = stored {
+        remove_output(&output_dir, &page.server_path).await?;
+        page.delete(&pool).await?;
+    }
+    Ok(())
 }
 
 async fn generate_all(
     changes: Vec<FilesChange>,
     root_dir: &PathBuf,
+    output_dir: &PathBuf,
     pool: &DbConnection,
     sender: &Sender<Message>,
 ) {
@@ -68,9 +366,12 @@ async fn generate_all(
                     let thread = async_std::task::spawn(async move {
                         let urls = generate_article_db(&path, &root_dir, &pool).await;
                         match urls {
-                            Ok(urls) => {
+                            Ok(Some(urls)) => {
                                 let _ = sender.send(Message::DbArticleCreated { path, urls }).await;
                             }
+                            Ok(None) => {
+                                let _ = sender.send(Message::DbArticleUnchanged { path }).await;
+                            }
                             Err(error) => {
                                 let _ = sender.send(Message::DbArticleError { path, error }).await;
                             }
@@ -80,21 +381,46 @@ async fn generate_all(
                 }
             }
             FilesChange::PagesChanged { files } => {
-                //
+                for path in files {
+                    let pool = pool.clone();
+                    let root_dir = root_dir.clone();
+                    let sender = sender.clone();
+                    let thread = async_std::task::spawn(async move {
+                        let urls = generate_page_db(&path, &root_dir, &pool).await;
+                        match urls {
+                            Ok(Some(urls)) => {
+                                let _ = sender.send(Message::DbPageCreated { path, urls }).await;
+                            }
+                            Ok(None) => {
+                                let _ = sender.send(Message::DbPageUnchanged { path }).await;
+                            }
+                            Err(error) => {
+                                let _ = sender.send(Message::DbPageError { path, error }).await;
+                            }
+                        };
+                    });
+                    generate_tasks.push(thread);
+                }
+            }
+            FilesChange::ArticlesDeleted { files } => {
+                for path in files {
+                    if let Err(error) = delete_article_db(&path, &output_dir, &pool).await {
+                        let _ = sender.send(Message::DbArticleError { path, error }).await;
+                    }
+                }
+            }
+            FilesChange::PagesDeleted { files } => {
+                for path in files {
+                    if let Err(error) = delete_page_db(&path, &output_dir, &pool).await {
+                        let _ = sender.send(Message::DbPageError { path, error }).await;
+                    }
+                }
             }
-            _ => (),
+            // Root changes require a full sync
+            FilesChange::RootChanged { .. } => (),
         }
     }
 
-    // for path in page_files {
-    //     let pool2 = pool.clone();
-    //     let root_dir = root_dir.clone();
-    //     let thread = async_std::task::spawn(async move {
-    //         Ok(generate_article_db(&path, &root_dir, &pool2).await?)
-    //     });
-    //     generate_threads.push(thread);
-    // }
-
     join_all(generate_tasks).await;
     let _ = sender.send(Message::DbGenerated).await;
 }
@@ -107,6 +433,7 @@ pub async fn sync(
     let article_dir = params.article_dir.clone();
     let pages_dir = params.pages_dir.clone();
     let root_dir = params.root_dir.clone();
+    let output_dir = params.output_dir.clone();
     // Get input markdown files
     let article_files = glob(&format!("{}/**/*.md", article_dir.to_string_lossy()))?
         .filter_map(Result::ok)
@@ -119,6 +446,7 @@ pub async fn sync(
         .collect::<Vec<PathBuf>>();
 
     Article::clean_non_existing(&dbc, article_files.as_slice()).await?;
+    Page::clean_non_existing(&dbc, page_files.as_slice()).await?;
 
     // Initially, we assume all files changed, before watch starts
     let msgs = vec![
@@ -131,6 +459,48 @@ pub async fn sync(
     let dbc = dbc.clone();
     let sender = sender.clone();
     Ok(async_std::task::spawn(async move {
-        generate_all(msgs, &root_dir, &dbc, &sender).await
+        generate_all(msgs, &root_dir, &output_dir, &dbc, &sender).await
+    }))
+}
+
+/// Generates the changed files, changes outside of articles and pages cause a
+/// full sync
+pub async fn sync_changes(
+    changes: Vec<FilesChange>,
+    params: &GenerateParams,
+    dbc: &DbConnection,
+    sender: &Sender<Message>,
+) -> Result<JoinHandle<()>, Error> {
+    if changes
+        .iter()
+        .any(|change| matches!(change, FilesChange::RootChanged { .. }))
+    {
+        return sync(&params, &dbc, &sender).await;
+    }
+
+    let root_dir = params.root_dir.clone();
+    let output_dir = params.output_dir.clone();
+    let dbc = dbc.clone();
+    let sender = sender.clone();
+    Ok(async_std::task::spawn(async move {
+        generate_all(changes, &root_dir, &output_dir, &dbc, &sender).await
     }))
 }
+
+#[cfg(test)]
+mod test_generate_db {
+    use super::is_reserved_path;
+
+    #[test]
+    fn test_is_reserved_path() {
+        assert!(is_reserved_path("/terms"));
+        assert!(is_reserved_path("/page"));
+        assert!(is_reserved_path("/atom.xml"));
+        assert!(is_reserved_path("/resources"));
+        assert!(is_reserved_path("/articles/post01"));
+        assert!(is_reserved_path("/page/2"));
+        assert!(!is_reserved_path("/pages"));
+        assert!(!is_reserved_path("/about/team"));
+        assert!(!is_reserved_path("/about"));
+    }
+}
@@ -0,0 +1,567 @@
+use crate::config::SiteConfig;
+use crate::db::models::{Article, Image, Page, Resource, Term};
+use crate::db::{DbConnection, Published};
+use crate::feeds::{absolute_urls, atom_feed, json_feed, ATOM_PATH, JSON_FEED_PATH};
+use crate::layout::{
+    article_page, index_page, index_path, redirect_page, static_page, term_page, terms_page,
+    OgImage, TERMS_PATH,
+};
+use crate::markdown::{theme_css, SYNTAX_CSS_PATH};
+use crate::redirects::{
+    htaccess_rules, netlify_rules, nginx_rules, redirects, HTACCESS_PATH, NETLIFY_PATH, NGINX_PATH,
+};
+use crate::resources::{clean_resource_outputs, copy_resource};
+use crate::search::{search_index, SEARCH_INDEX_PATH};
+use crate::sitemap::{robots_txt, sitemap, ROBOTS_PATH, SITEMAP_PATH};
+use crate::urls::{file_urls, replace_file_urls};
+use crate::GenerateParams;
+use async_std::fs;
+use async_std::path::PathBuf;
+use derive_more::From;
+use percent_encoding::percent_decode_str;
+use std::collections::{HashMap, HashSet};
+use url::Url;
+
+#[derive(Debug, From)]
+pub enum Error {
+    IOError(std::io::Error),
+    DbError(crate::db::Error),
+    ResourceError(crate::resources::Error),
+    JsonError(serde_json::Error),
+    HighlightError(crate::markdown::HighlightError),
+    LinkError {
+        path: String,
+        error: crate::urls::Error,
+    },
+}
+
+/// Output file of the server path, e.g. `/articles/post01` is written to
+/// `articles/post01/index.html` inside the output directory
+fn output_file(output_dir: &PathBuf, server_path: &str) -> PathBuf {
+    let mut file = output_dir.clone();
+    for part in server_path.split('/').filter(|p| !p.is_empty()) {
+        file.push(part);
+    }
+    file.push("index.html");
+    file
+}
+
+/// Writes the file, unless it already has the same contents
+async fn write_file(file: &PathBuf, contents: &str) -> Result<(), Error> {
+    if file.exists().await && fs::read_to_string(file).await? == contents {
+        return Ok(());
+    }
+    if let Some(dir) = file.parent() {
+        fs::create_dir_all(dir).await?;
+    }
+    fs::write(file, contents).await?;
+    Ok(())
+}
+
+/// Removes the output of the server path
+pub async fn remove_output(output_dir: &PathBuf, server_path: &str) -> Result<(), Error> {
+    let file = output_file(&output_dir, server_path);
+    if file.exists().await {
+        fs::remove_file(&file).await?;
+    }
+
+    // Directory is left in place if it has other outputs
+    if let Some(dir) = file.parent() {
+        let _ = fs::remove_dir(dir).await;
+    }
+    Ok(())
+}
+
+/// Server paths of the local files by their file:// url, unpublished
+/// articles have none
+async fn server_paths(
+    dbc: &DbConnection,
+    published: Published,
+) -> Result<HashMap<String, String>, Error> {
+    let mut server_paths = HashMap::new();
+    let mut add = |local_path: &str, server_path: String| {
+        if let Ok(url) = Url::from_file_path(local_path) {
+            server_paths.insert(url.to_string(), server_path);
+        }
+    };
+
+    for article in Article::get_all(&dbc).await? {
+        if published.includes(&article) {
+            add(&article.local_path, article.server_path);
+        }
+    }
+
+    for page in Page::get_all(&dbc).await? {
+        add(&page.local_path, page.server_path);
+    }
+
+    for resource in Resource::get_all(&dbc).await? {
+        if PathBuf::from(&resource.local_path).exists().await {
+            add(&resource.local_path, resource.server_path);
+        }
+    }
+
+    Ok(server_paths)
+}
+
+/// The file:// urls of the articles that are not published, links to them
+/// are shown as plain text
+async fn unpublished_urls(
+    dbc: &DbConnection,
+    published: Published,
+) -> Result<HashSet<String>, Error> {
+    let mut unpublished = HashSet::new();
+    for article in Article::get_all(&dbc).await? {
+        if published.includes(&article) {
+            continue;
+        }
+        if let Ok(url) = Url::from_file_path(&article.local_path) {
+            unpublished.insert(url.to_string());
+        }
+    }
+    Ok(unpublished)
+}
+
+/// Replaces the file:// urls of the document's html
+fn resolve_links(
+    html: &str,
+    local_path: &str,
+    server_paths: &HashMap<String, String>,
+    unpublished: &HashSet<String>,
+) -> Result<String, Error> {
+    replace_file_urls(html, server_paths, unpublished).map_err(|error| Error::LinkError {
+        path: local_path.into(),
+        error,
+    })
+}
+
+/// Checks the links of all articles and pages, without writing anything
+///
+/// Drafts and scheduled articles may link to each other, published documents
+/// may link to them too but the links are shown as plain text. Returns the
+/// documents with links to unknown files.
+pub async fn check_links(params: &GenerateParams, dbc: &DbConnection) -> Result<Vec<Error>, Error> {
+    let published = params.published();
+    let public_paths = server_paths(&dbc, published).await?;
+    let unpublished = unpublished_urls(&dbc, published).await?;
+    let everything = Published {
+        drafts: true,
+        until: chrono::NaiveDateTime::MAX,
+    };
+    let all_paths = server_paths(&dbc, everything).await?;
+    let none = HashSet::new();
+
+    let mut documents = vec![];
+    for article in Article::get_all(&dbc).await? {
+        let (server_paths, unpublished) = if published.includes(&article) {
+            (&public_paths, &unpublished)
+        } else {
+            (&all_paths, &none)
+        };
+        documents.push((article.local_path, article.html, server_paths, unpublished));
+    }
+    for page in Page::get_all(&dbc).await? {
+        documents.push((page.local_path, page.html, &public_paths, &unpublished));
+    }
+
+    Ok(documents
+        .into_iter()
+        .filter_map(|(path, html, server_paths, unpublished)| {
+            resolve_links(&html, &path, server_paths, unpublished).err()
+        })
+        .collect())
+}
+
+/// Removes the resources that no document refers to, or whose file is gone,
+/// with their outputs, and returns the remaining ones
+async fn clean_resources(
+    params: &GenerateParams,
+    dbc: &DbConnection,
+) -> Result<Vec<Resource>, Error> {
+    let mut urls = vec![];
+    for article in Article::get_all(&dbc).await? {
+        urls.extend(file_urls(&article.html));
+    }
+    for page in Page::get_all(&dbc).await? {
+        urls.extend(file_urls(&page.html));
+    }
+
+    let mut used = vec![];
+    for url in urls {
+        if let Ok(path) = url.to_file_path() {
+            let path: PathBuf = path.into();
+            if path.exists().await {
+                used.push(path.to_string_lossy().into_owned());
+            }
+        }
+    }
+    Resource::clean_unused(&dbc, &used).await?;
+
+    let resources = Resource::get_all(&dbc).await?;
+    clean_resource_outputs(&resources, &params.output_dir).await?;
+    Ok(resources)
+}
+
+/// First image of the document with its dimensions, for sharing previews
+async fn og_image(
+    html: &str,
+    server_paths: &HashMap<String, String>,
+    site: &SiteConfig,
+    dbc: &DbConnection,
+) -> Result<Option<OgImage>, Error> {
+    for url in file_urls(html) {
+        let (path, server_path) = match (url.to_file_path(), server_paths.get(url.as_str())) {
+            (Ok(path), Some(server_path)) => (path, server_path),
+            _ => continue,
+        };
+        if let Some(image) = Image::get_by_local_path(&dbc, &path.to_string_lossy()).await? {
+            return Ok(Some(OgImage {
+                url: site.url(server_path),
+                size: Some((image.width, image.height)),
+            }));
+        }
+    }
+    Ok(None)
+}
+
+/// Renders all articles and pages from the database to the output directory
+pub async fn generate_html(params: &GenerateParams, dbc: &DbConnection) -> Result<(), Error> {
+    if params.clean_output && params.output_dir.exists().await {
+        fs::remove_dir_all(&params.output_dir).await?;
+    }
+
+    for resource in clean_resources(&params, &dbc).await? {
+        copy_resource(&resource, &params.output_dir).await?;
+    }
+
+    let published = params.published();
+    let server_paths = server_paths(&dbc, published).await?;
+    let unpublished = unpublished_urls(&dbc, published).await?;
+
+    // Redirect pages are written first, the pages of this site replace them
+    write_redirects(&params, &dbc, published).await?;
+
+    for mut article in Article::get_all(&dbc).await? {
+        if !published.includes(&article) {
+            remove_output(&params.output_dir, &article.server_path).await?;
+            continue;
+        }
+        let image = og_image(&article.html, &server_paths, &params.site, &dbc).await?;
+        article.html = resolve_links(
+            &article.html,
+            &article.local_path,
+            &server_paths,
+            &unpublished,
+        )?;
+        let file = output_file(&params.output_dir, &article.server_path);
+        write_file(&file, &article_page(&article, image, &params.site)).await?;
+    }
+
+    for mut page in Page::get_all(&dbc).await? {
+        page.html = resolve_links(&page.html, &page.local_path, &server_paths, &unpublished)?;
+        let file = output_file(&params.output_dir, &page.server_path);
+        write_file(&file, &static_page(&page, &params.site)).await?;
+    }
+
+    write_index(&params, &dbc, published, &server_paths, &unpublished).await?;
+    write_terms(&params, &dbc, published, &server_paths, &unpublished).await?;
+    write_feeds(&params, &dbc, published, &server_paths, &unpublished).await?;
+    write_sitemap(&params, &dbc, published).await?;
+    write_search_index(&params, &dbc, published).await?;
+
+    let syntax_css_file = params
+        .output_dir
+        .join(SYNTAX_CSS_PATH.trim_start_matches('/'));
+    write_file(&syntax_css_file, &theme_css(&params.site.highlight_theme)?).await?;
+
+    Ok(())
+}
+
+/// Writes the pages of the article index, and removes the pages left over
+/// from previous generations
+async fn write_index(
+    params: &GenerateParams,
+    dbc: &DbConnection,
+    published: Published,
+    server_paths: &HashMap<String, String>,
+    unpublished: &HashSet<String>,
+) -> Result<(), Error> {
+    let page_size = params.site.page_size.max(1);
+    let count = Article::count(&dbc, published).await? as usize;
+    let pages = ((count + page_size - 1) / page_size).max(1);
+
+    for page in 1..=pages {
+        let offset = ((page - 1) * page_size) as i64;
+        let mut articles = Article::get_page(&dbc, published, offset, page_size as i64).await?;
+        for article in &mut articles {
+            article.excerpt = resolve_links(
+                &article.excerpt,
+                &article.local_path,
+                server_paths,
+                unpublished,
+            )?;
+        }
+        let file = output_file(&params.output_dir, &index_path(page));
+        write_file(&file, &index_page(&articles, page, pages, &params.site)).await?;
+    }
+
+    let mut page = pages + 1;
+    while output_file(&params.output_dir, &index_path(page))
+        .exists()
+        .await
+    {
+        remove_output(&params.output_dir, &index_path(page)).await?;
+        page += 1;
+    }
+    Ok(())
+}
+
+/// Writes the pages of the categories and tags, and their overview
+async fn write_terms(
+    params: &GenerateParams,
+    dbc: &DbConnection,
+    published: Published,
+    server_paths: &HashMap<String, String>,
+    unpublished: &HashSet<String>,
+) -> Result<(), Error> {
+    for unused in Term::clean_unused(&dbc).await? {
+        remove_output(&params.output_dir, &unused.server_path).await?;
+    }
+
+    let mut counts = vec![];
+    for term in Term::get_all(&dbc).await? {
+        let mut articles = term.get_articles(&dbc, published).await?;
+        if articles.is_empty() {
+            remove_output(&params.output_dir, &term.server_path).await?;
+            continue;
+        }
+        for article in &mut articles {
+            article.excerpt = resolve_links(
+                &article.excerpt,
+                &article.local_path,
+                server_paths,
+                unpublished,
+            )?;
+        }
+        let file = output_file(&params.output_dir, &term.server_path);
+        write_file(&file, &term_page(&term, &articles, &params.site)).await?;
+        counts.push((term, articles.len()));
+    }
+
+    let file = output_file(&params.output_dir, TERMS_PATH);
+    write_file(&file, &terms_page(&counts, &params.site)).await?;
+    Ok(())
+}
+
+/// Writes the feeds of the articles, with absolute urls in their contents
+async fn write_feeds(
+    params: &GenerateParams,
+    dbc: &DbConnection,
+    published: Published,
+    server_paths: &HashMap<String, String>,
+    unpublished: &HashSet<String>,
+) -> Result<(), Error> {
+    let absolute_urls = absolute_urls(&server_paths, &params.site);
+    let mut articles = vec![];
+    for mut article in Article::get_newest(&dbc, published).await? {
+        article.html = resolve_links(
+            &article.html,
+            &article.local_path,
+            &absolute_urls,
+            unpublished,
+        )?;
+        let terms = article.get_terms(&dbc).await?;
+        articles.push((article, terms));
+    }
+
+    let atom_file = params.output_dir.join(ATOM_PATH.trim_start_matches('/'));
+    write_file(&atom_file, &atom_feed(&articles, &params.site)).await?;
+
+    let json_feed_file = params
+        .output_dir
+        .join(JSON_FEED_PATH.trim_start_matches('/'));
+    write_file(&json_feed_file, &json_feed(&articles, &params.site)?).await?;
+    Ok(())
+}
+
+/// Output file of the redirect page, old urls like `/2019/post.html` are
+/// written as is
+fn redirect_file(output_dir: &PathBuf, path: &str) -> PathBuf {
+    let last = path.rsplit('/').next().unwrap_or("");
+    if last.contains('.') {
+        output_dir.join(path.trim_start_matches('/'))
+    } else {
+        output_file(&output_dir, path)
+    }
+}
+
+/// Writes the redirect rules of the old urls, and redirect pages for the old
+/// urls of this site
+async fn write_redirects(
+    params: &GenerateParams,
+    dbc: &DbConnection,
+    published: Published,
+) -> Result<(), Error> {
+    let articles: Vec<Article> = Article::get_all(&dbc)
+        .await?
+        .into_iter()
+        .filter(|article| published.includes(article))
+        .collect();
+    let redirects = redirects(&articles, &params.site);
+
+    for redirect in redirects.iter().filter(|redirect| redirect.same_host) {
+        // Output directory is served from the base url
+        let path = match redirect.from.strip_prefix(params.site.base_url.path()) {
+            Some(path) if !path.is_empty() => format!("/{}", path),
+            _ => continue,
+        };
+        let path = percent_decode_str(&path).decode_utf8_lossy();
+        if path.split('/').any(|part| part == "..") {
+            continue;
+        }
+        let file = redirect_file(&params.output_dir, &path);
+        write_file(&file, &redirect_page(&redirect.to)).await?;
+    }
+
+    for (server_path, rules) in &[
+        (NGINX_PATH, nginx_rules(&redirects)),
+        (HTACCESS_PATH, htaccess_rules(&redirects)),
+        (NETLIFY_PATH, netlify_rules(&redirects)),
+    ] {
+        let file = params.output_dir.join(server_path.trim_start_matches('/'));
+        write_file(&file, rules).await?;
+    }
+    Ok(())
+}
+
+/// Writes the sitemap of the articles and pages, and the robots.txt pointing
+/// to it
+async fn write_sitemap(
+    params: &GenerateParams,
+    dbc: &DbConnection,
+    published: Published,
+) -> Result<(), Error> {
+    let articles: Vec<Article> = Article::get_all(&dbc)
+        .await?
+        .into_iter()
+        .filter(|article| published.includes(article))
+        .collect();
+    let pages = Page::get_all(&dbc).await?;
+
+    let sitemap_file = params.output_dir.join(SITEMAP_PATH.trim_start_matches('/'));
+    write_file(&sitemap_file, &sitemap(&articles, &pages, &params.site)).await?;
+
+    let robots_file = params.output_dir.join(ROBOTS_PATH.trim_start_matches('/'));
+    write_file(&robots_file, &robots_txt(&params.site)).await?;
+    Ok(())
+}
+
+/// Writes the search index of the published articles and the pages
+async fn write_search_index(
+    params: &GenerateParams,
+    dbc: &DbConnection,
+    published: Published,
+) -> Result<(), Error> {
+    let mut articles = vec![];
+    for article in Article::get_newest(&dbc, published).await? {
+        let terms = article.get_terms(&dbc).await?;
+        articles.push((article, terms));
+    }
+    let pages = Page::get_all(&dbc).await?;
+
+    let index_file = params
+        .output_dir
+        .join(SEARCH_INDEX_PATH.trim_start_matches('/'));
+    write_file(
+        &index_file,
+        &search_index(&articles, &pages, &params.site.search)?,
+    )
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test_output_file {
+    use super::{output_file, redirect_file};
+    use async_std::path::PathBuf;
+
+    #[test]
+    fn test_output_file() {
+        let output_dir: PathBuf = ".out".into();
+        assert_eq!(
+            output_file(&output_dir, "/articles/post01"),
+            output_dir
+                .join("articles")
+                .join("post01")
+                .join("index.html")
+        );
+        assert_eq!(output_file(&output_dir, "/"), output_dir.join("index.html"));
+    }
+
+    #[test]
+    fn test_redirect_file() {
+        let output_dir: PathBuf = ".out".into();
+        assert_eq!(
+            redirect_file(&output_dir, "/2019/post.html"),
+            output_dir.join("2019").join("post.html")
+        );
+        assert_eq!(
+            redirect_file(&output_dir, "/2019/post/"),
+            output_dir.join("2019").join("post").join("index.html")
+        );
+    }
+}
+
+#[cfg(test)]
+mod test_resolve_links {
+    use super::{resolve_links, Error};
+    use std::collections::{HashMap, HashSet};
+
+    fn server_paths() -> HashMap<String, String> {
+        let mut server_paths = HashMap::new();
+        server_paths.insert(
+            "file:///blog/articles/post01.md".to_owned(),
+            "/articles/post01".to_owned(),
+        );
+        server_paths
+    }
+
+    #[test]
+    fn test_link_to_unpublished() {
+        let mut unpublished = HashSet::new();
+        unpublished.insert("file:///blog/articles/draft.md".to_owned());
+
+        // The draft is not published, the build goes on without the link
+        let html = r#"<a href="file:///blog/articles/post01.md">Post 01</a>
+            <a href="file:///blog/articles/draft.md">Draft</a>"#;
+        assert_eq!(
+            resolve_links(
+                html,
+                "/blog/articles/post02.md",
+                &server_paths(),
+                &unpublished
+            )
+            .unwrap(),
+            r#"<a href="/articles/post01">Post 01</a>
+            <a>Draft</a>"#
+        );
+    }
+
+    #[test]
+    fn test_link_to_missing() {
+        let mut unpublished = HashSet::new();
+        unpublished.insert("file:///blog/articles/draft.md".to_owned());
+
+        // Unknown files fail the build
+        let html = r#"<a href="file:///blog/articles/missing.md">Missing</a>"#;
+        match resolve_links(
+            html,
+            "/blog/articles/post02.md",
+            &server_paths(),
+            &unpublished,
+        ) {
+            Err(Error::LinkError { path, .. }) => assert_eq!(path, "/blog/articles/post02.md"),
+            other => panic!("expected link error, got {:?}", other),
+        }
+    }
+}
@@ -8,6 +8,9 @@ use derive_more::From;
 #[derive(Debug, From)]
 pub enum Error {
     FilePathSeparationError,
+
+    /// The file is not committed, e.g. a new draft
+    NoHistory,
     SignalTerminated,
     ExitError(i32, String),
     DateParseError(ParseError),
@@ -46,6 +49,9 @@ async fn git_date(file: &PathBuf, flag: Option<&str>) -> Result<DateTime<Utc>, E
     match out.status.code() {
         Some(0) => {
             let out_str = String::from_utf8_lossy(&out.stdout);
+            if out_str.trim().is_empty() {
+                return Err(Error::NoHistory);
+            }
             let datetime = DateTime::parse_from_rfc2822(&out_str)?.into();
             Ok(datetime)
         }
@@ -67,7 +73,8 @@ pub async fn git_modified(file: &PathBuf) -> Result<DateTime<Utc>, Error> {
 
 #[cfg(test)]
 mod test_git_date {
-    use super::{git_added, git_modified};
+    use super::{git_added, git_modified, Error};
+    use async_std::{fs, path::PathBuf};
     use chrono::{TimeZone, Utc};
 
     #[async_std::test]
@@ -85,4 +92,15 @@ mod test_git_date {
             .unwrap();
         assert_eq!(created, Utc.ymd(2021, 1, 3).and_hms(12, 42, 37));
     }
+
+    #[async_std::test]
+    async fn test_git_no_history() {
+        let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target");
+        fs::create_dir_all(&dir).await.unwrap();
+        let file = dir.join("uncommitted.md");
+        fs::write(&file, "# Draft").await.unwrap();
+
+        assert!(matches!(git_added(&file).await, Err(Error::NoHistory)));
+        assert!(matches!(git_modified(&file).await, Err(Error::NoHistory)));
+    }
 }
@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+
+use async_std::path::PathBuf;
+use derive_more::From;
+use regex::{Captures, Regex};
+use url::Url;
+
+use crate::db::{models::Image, DbConnection, ImageId};
+use crate::fingerprint::modified_on_disk;
+
+#[derive(Debug, From)]
+pub enum Error {
+    IOError(std::io::Error),
+    ImageSizeError(imagesize::ImageError),
+    DbError(crate::db::Error),
+}
+
+/// Is the file an image with probable dimensions
+pub fn is_image(path: &PathBuf) -> bool {
+    match path.extension() {
+        Some(ext) => matches!(
+            &ext.to_string_lossy().to_lowercase() as &str,
+            "png" | "jpg" | "jpeg" | "gif" | "webp" | "bmp"
+        ),
+        None => false,
+    }
+}
+
+/// Stores the image dimensions in the database
+///
+/// The image is probed only if it has been modified since it was stored.
+pub async fn generate_image_db(image_file: &PathBuf, dbc: &DbConnection) -> Result<Image, Error> {
+    let local_path = image_file.to_string_lossy().into_owned();
+    let modified_on_disk = modified_on_disk(&image_file).await?;
+
+    let existing = Image::get_by_local_path(&dbc, &local_path).await?;
+    if let Some(image) = existing {
+        if image.modified_on_disk == modified_on_disk {
+            return Ok(image);
+        }
+    }
+
+    let file = image_file.clone();
+    let size = async_std::task::spawn_blocking(move || imagesize::size(file)).await?;
+    let image = Image {
+        id: ImageId::new(),
+        modified_on_disk,
+        width: size.width as i32,
+        height: size.height as i32,
+        local_path,
+    };
+    image.save(&dbc).await?;
+    Ok(image)
+}
+
+/// Adds width and height to the `<img>` tags pointing to the given images
+pub fn set_image_sizes(html: &str, images: &[Image]) -> String {
+    let sizes = images
+        .iter()
+        .filter_map(|image| {
+            let url = Url::from_file_path(&image.local_path).ok()?;
+            Some((url.to_string(), (image.width, image.height)))
+        })
+        .collect::<HashMap<_, _>>();
+
+    let reg = Regex::new(r#"<img src="([^"]*?)""#).unwrap();
+    reg.replace_all(html, |caps: &Captures| match sizes.get(&caps[1]) {
+        Some((width, height)) => format!(r#"{} width="{}" height="{}""#, &caps[0], width, height),
+        None => caps[0].to_owned(),
+    })
+    .into_owned()
+}
+
+#[cfg(test)]
+mod test_images {
+    use super::{is_image, set_image_sizes};
+    use crate::db::{models::Image, ImageId};
+    use url::Url;
+
+    #[test]
+    fn test_is_image() {
+        assert!(is_image(&"./examples/articles/photo.JPG".into()));
+        assert!(!is_image(&"./examples/articles/res01.svg".into()));
+        assert!(!is_image(&"./examples/articles/post01.md".into()));
+    }
+
+    #[test]
+    fn test_set_image_sizes() {
+        let path = std::env::current_dir().unwrap().join("photo.png");
+        let url = Url::from_file_path(&path).unwrap();
+        let image = Image {
+            id: ImageId::new(),
+            modified_on_disk: chrono::Local::now().naive_utc(),
+            width: 1600,
+            height: 900,
+            local_path: path.to_string_lossy().into_owned(),
+        };
+        let html = format!(
+            r#"<img src="{}" alt="Photo" /><img src="file:///other.png" alt="" />"#,
+            url
+        );
+        assert_eq!(
+            set_image_sizes(&html, &[image]),
+            format!(
+                r#"<img src="{}" width="1600" height="900" alt="Photo" /><img src="file:///other.png" alt="" />"#,
+                url
+            )
+        );
+    }
+}
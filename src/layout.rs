@@ -1,5 +1,9 @@
 #![allow(unused_braces)]
-use render::{component, html::HTML5Doctype, rsx, Render};
+use crate::config::SiteConfig;
+use crate::db::models::{Article, Page, Term};
+use crate::markdown::{toc_html, TocHeading, INLINE_TOC_START, SYNTAX_CSS_PATH};
+use chrono::TimeZone;
+use render::{component, html, html::HTML5Doctype, rsx, Raw, Render};
 
 // macro_rules! oddstruct {
 //     ($n:ident, $t:type ) => {
@@ -27,11 +31,21 @@ enum OgType {
     },
 }
 
+/// Image shown in the sharing previews
+#[derive(Debug)]
+pub struct OgImage {
+    pub url: url::Url,
+
+    /// Width and height, if known
+    pub size: Option<(i32, i32)>,
+}
+
+/// Open Graph metadata, see https://ogp.me/
 #[derive(Debug)]
 struct Og {
     // Required
     title: String,
-    image: url::Url,
+    image: Option<OgImage>,
     url: url::Url,
 
     // Optional, but not for my implementation
@@ -50,14 +64,30 @@ fn OgHead(og: Og) {
             <link rel={"canonical"} href={og.url.to_string()} />
             <meta property={"og:url"} content={og.url.to_string()} />
             <meta property={"og:title"} content={og.title} />
-            <meta property={"og:image"} content={og.image.to_string()} />
-            // <meta property={"og:image:width"} content={image.1.to_string()} />
-            // <meta property={"og:image:height"} content={image.2.to_string()} />
+            {og.image.map(|image| rsx! {
+                <>
+                    <meta property={"og:image"} content={image.url.to_string()} />
+                    {image.size.map(|(width, height)| rsx! {
+                        <>
+                            <meta property={"og:image:width"} content={width.to_string()} />
+                            <meta property={"og:image:height"} content={height.to_string()} />
+                        </>
+                    })}
+                </>
+            })}
 
             <meta property={"og:locale"} content={og.locale} />
             <meta property={"og:description"} content={og.description} />
             <meta property={"og:site_name"} content={og.site_name} />
 
+            // Articles without a configured author leave the author out
+            {match &og.ogtype {
+                OgType::Article { author, .. } if !author.is_empty() => Some(rsx! {
+                    <meta property={"article:author"} content={author.clone()} />
+                }),
+                _ => None,
+            }}
+
             {match og.ogtype {
                 OgType::Website => rsx! {
                     <>
@@ -66,13 +96,11 @@ fn OgHead(og: Og) {
                         // Render-rs seems to assume each same typed rsx! block must have same amount of childs...
                         <meta />
                         <meta />
-                        <meta />
                     </>
                 },
-                OgType::Article { author, published, modified } => rsx! {
+                OgType::Article { published, modified, .. } => rsx! {
                     <>
                         <meta property={"og:type"} content={"article"} />
-                        <meta property={"article:author"} content={author} />
                         <meta property={"article:published_time"} content={iso8601(published)} />
                         <meta property={"article:modified_time"} content={iso8601(modified)} />
                         // <meta property={"article:publisher"} content={"https://www.facebook.com/YOURFBPAGE"} />
@@ -82,76 +110,24 @@ fn OgHead(og: Og) {
         </>
     }
 }
-///
-///
-/// og:article: https://ogp.me/#type_article
+
 #[component]
-fn OgGraph<'a, 'b, 'c, 'd, 'e>(
+fn Html<'a, 'b, 'c, Children: Render>(
     title: &'a str,
     description: &'b str,
-    image: (&'c str, u32, u32),
-    canonical_url: &'d str,
-    og_type: &'e str,
-    article_published: chrono::DateTime<chrono::Utc>,
-    article_modified: chrono::DateTime<chrono::Utc>,
-    site_name: &'static str,
-    locale: &'static str,
+    noindex: bool,
+    og: Og,
+    site: &'c SiteConfig,
+    children: Children,
 ) {
-    rsx! {
-        <>
-            <meta name={"description"} content={description} />
-            <meta name={"robots"} content={"index, follow"} />
-            <link rel={"canonical"} href={canonical_url} />
-            <meta property={"og:url"} content={canonical_url} />
-            <meta property={"og:locale"} content={locale} />
-            <meta property={"og:title"} content={title} />
-            <meta property={"og:description"} content={description} />
-            <meta property={"og:site_name"} content={site_name} />
-
-            <meta property={"og:image"} content={image.0} />
-            <meta property={"og:image:width"} content={image.1.to_string()} />
-            <meta property={"og:image:height"} content={image.2.to_string()} />
-
-            // Type specifics:
-            <meta property={"og:type"} content={og_type} />
-            <meta property={"article:published_time"} content={iso8601(article_published)} />
-            <meta property={"article:modified_time"} content={iso8601(article_modified)} />
-            // <meta property={"article:publisher"} content={"https://www.facebook.com/YOURFBPAGE"} />
-            // <meta property={"article:author"} content={"John Doe"} />
-
-            // <meta name={"twitter:card"} content={"summary"} />
-        </>
-    }
-}
-
-#[component]
-fn Html<'a, 'b, Children: Render>(title: &'a str, description: &'b str, children: Children) {
     rsx! { <>
        <HTML5Doctype />
        <html>
          <head>
             <title>{title}</title>
-            <meta name={"robots"} content={"index, follow"} />
-            <OgHead og={(Og {
-                title: "test".into(),
-                url: url::Url::parse("https://example.com/").unwrap(),
-                image: url::Url::parse("https://example.com/image.jpg").unwrap(),
-                description: "test".into(),
-                site_name: "My Thoughts".into(),
-                locale: "en_US".into(),
-                ogtype: OgType::Website,
-            })} />
-            // <OgGraph
-            //     title={title}
-            //     description={description}
-            //     image={("https://example.com/foo.jpg", 1600, 900)}
-            //     site_name={"My Thoughts"}
-            //     canonical_url={"https://example.com"}
-            //     article_published={chrono::Utc::now()}
-            //     article_modified={chrono::Utc::now()}
-            //     locale={"en_GB"}
-            //     og_type={"article"}
-            //     />
+            <meta name={"robots"} content={if noindex { "noindex, follow" } else { "index, follow" }} />
+            <OgHead og={og} />
+            <link rel={"stylesheet"} href={site.url(SYNTAX_CSS_PATH).to_string()} />
         </head>
          <body>
            {children}
@@ -165,18 +141,323 @@ fn Heading(title: String, fullname: String) {
     rsx! { <h1 class={"title"}>{title}{" "}{fullname}</h1> }
 }
 
+/// Default image of the site, without known dimensions
+fn site_image(site: &SiteConfig) -> Option<OgImage> {
+    site.image_url().map(|url| OgImage { url, size: None })
+}
+
+/// Open Graph metadata of the site's own pages
+fn website_og(title: &str, server_path: &str, site: &SiteConfig) -> Og {
+    Og {
+        title: title.into(),
+        image: site_image(&site),
+        url: site.url(server_path),
+        description: "".into(),
+        site_name: site.site_name.clone(),
+        locale: site.locale.clone(),
+        ogtype: OgType::Website,
+    }
+}
+
+/// Open Graph metadata of the article, `image` is the first image of the
+/// article if it has one
+fn article_og(article: &Article, image: Option<OgImage>, site: &SiteConfig) -> Og {
+    Og {
+        title: article.title.clone(),
+        image: image.or_else(|| site_image(&site)),
+        url: site.url(&article.server_path),
+        description: article.description.clone(),
+        site_name: site.site_name.clone(),
+        locale: site.locale.clone(),
+        ogtype: OgType::Article {
+            author: site.author.clone(),
+            published: chrono::Utc.from_utc_datetime(&article.published),
+            modified: chrono::Utc.from_utc_datetime(&article.modified),
+        },
+    }
+}
+
+/// Table of contents of the long documents, which have at least
+/// `toc_min_headings` headings, unless the document has one in place of the
+/// `[TOC]` placeholder
+fn long_toc(toc: &str, html: &str, site: &SiteConfig) -> Option<String> {
+    if site.toc_min_headings == 0 || toc.is_empty() || html.contains(INLINE_TOC_START) {
+        return None;
+    }
+    let toc: Vec<TocHeading> = match serde_json::from_str(toc) {
+        Ok(toc) => toc,
+        Err(error) => {
+            println!("Invalid table of contents: {:?}", error);
+            return None;
+        }
+    };
+    if TocHeading::count(&toc) < site.toc_min_headings {
+        return None;
+    }
+    Some(toc_html(&toc))
+}
+
+fn reading_time(minutes: i32) -> String {
+    format!("{} min read", minutes.max(1))
+}
+
+/// Full HTML document of the article
+pub fn article_page(article: &Article, image: Option<OgImage>, site: &SiteConfig) -> String {
+    let toc = long_toc(&article.toc, &article.html, &site);
+    html! {
+        <Html
+            title={article.title.as_str()}
+            description={article.description.as_str()}
+            noindex={article.noindex}
+            og={article_og(&article, image, &site)}
+            site={site}>
+            <article>
+                <h1>{article.title.as_str()}</h1>
+                <p class={"reading-time"}>{reading_time(article.reading_time)}</p>
+                {toc.as_ref().map(|toc| rsx! {
+                    <nav class={"toc"}>{Raw::from(toc.as_str())}</nav>
+                })}
+                {Raw::from(article.html.as_str())}
+            </article>
+        </Html>
+    }
+}
+
+/// Full HTML document of the page
+pub fn static_page(page: &Page, site: &SiteConfig) -> String {
+    let toc = long_toc(&page.toc, &page.html, &site);
+    html! {
+        <Html
+            title={page.title.as_str()}
+            description={""}
+            noindex={page.noindex}
+            og={website_og(&page.title, &page.server_path, &site)}
+            site={site}>
+            <main>
+                <h1>{page.title.as_str()}</h1>
+                {toc.as_ref().map(|toc| rsx! {
+                    <nav class={"toc"}>{Raw::from(toc.as_str())}</nav>
+                })}
+                {Raw::from(page.html.as_str())}
+            </main>
+        </Html>
+    }
+}
+
+/// Server path of the index page, the first page is the home page
+pub fn index_path(page: usize) -> String {
+    if page <= 1 {
+        "/".into()
+    } else {
+        format!("/page/{}", page)
+    }
+}
+
+#[component]
+fn ArticleSummary<'a, 'b>(article: &'a Article, site: &'b SiteConfig) {
+    rsx! {
+        <article>
+            <h2>
+                <a href={site.url(&article.server_path).to_string()}>{article.title.as_str()}</a>
+            </h2>
+            <time datetime={iso8601(chrono::Utc.from_utc_datetime(&article.published))}>
+                {article.published.format("%Y-%m-%d").to_string()}
+            </time>
+            <span class={"reading-time"}>{reading_time(article.reading_time)}</span>
+            {Raw::from(article.excerpt.as_str())}
+        </article>
+    }
+}
+
+#[component]
+fn Pagination<'a>(page: usize, pages: usize, site: &'a SiteConfig) {
+    rsx! {
+        <nav class={"pagination"}>
+            {if page > 1 {
+                Some(rsx! {
+                    <a rel={"prev"} href={site.url(&index_path(page - 1)).to_string()}>{"Newer"}</a>
+                })
+            } else {
+                None
+            }}
+            {if page < pages {
+                Some(rsx! {
+                    <a rel={"next"} href={site.url(&index_path(page + 1)).to_string()}>{"Older"}</a>
+                })
+            } else {
+                None
+            }}
+        </nav>
+    }
+}
+
+/// Full HTML document of the index page, listing the articles of the page
+pub fn index_page(articles: &[Article], page: usize, pages: usize, site: &SiteConfig) -> String {
+    let title = if page > 1 {
+        format!("{} - Page {}", site.site_name, page)
+    } else {
+        site.site_name.clone()
+    };
+    html! {
+        <Html
+            title={title.as_str()}
+            description={""}
+            noindex={false}
+            og={website_og(&title, &index_path(page), &site)}
+            site={site}>
+            <main>
+                {articles
+                    .iter()
+                    .map(|article| rsx! { <ArticleSummary article={article} site={site} /> })
+                    .collect::<Vec<_>>()}
+                <Pagination page={page} pages={pages} site={site} />
+            </main>
+        </Html>
+    }
+}
+
+/// Server path of the overview of the categories and tags
+pub const TERMS_PATH: &str = "/terms";
+
+/// Full HTML document of the category or tag, listing its articles
+pub fn term_page(term: &Term, articles: &[Article], site: &SiteConfig) -> String {
+    html! {
+        <Html
+            title={term.name.as_str()}
+            description={""}
+            noindex={false}
+            og={website_og(&term.name, &term.server_path, &site)}
+            site={site}>
+            <main>
+                <h1>{term.name.as_str()}</h1>
+                {articles
+                    .iter()
+                    .map(|article| rsx! { <ArticleSummary article={article} site={site} /> })
+                    .collect::<Vec<_>>()}
+            </main>
+        </Html>
+    }
+}
+
+#[component]
+fn TermList<'a, 'b, 'c>(terms: &'a [(Term, usize)], kind: &'b str, site: &'c SiteConfig) {
+    rsx! {
+        <ul>
+            {terms
+                .iter()
+                .filter(|(term, _)| term.kind == kind)
+                .map(|(term, count)| rsx! {
+                    <li>
+                        <a href={site.url(&term.server_path).to_string()}>{term.name.as_str()}</a>
+                        {format!(" ({})", count)}
+                    </li>
+                })
+                .collect::<Vec<_>>()}
+        </ul>
+    }
+}
+
+/// Full HTML document of the categories and tags, with their article counts
+pub fn terms_page(terms: &[(Term, usize)], site: &SiteConfig) -> String {
+    html! {
+        <Html
+            title={"Categories and tags"}
+            description={""}
+            noindex={false}
+            og={website_og("Categories and tags", TERMS_PATH, &site)}
+            site={site}>
+            <main>
+                <h1>{"Categories and tags"}</h1>
+                <h2>{"Categories"}</h2>
+                <TermList terms={terms} kind={Term::CATEGORY} site={site} />
+                <h2>{"Tags"}</h2>
+                <TermList terms={terms} kind={Term::TAG} site={site} />
+            </main>
+        </Html>
+    }
+}
+
+/// Page redirecting the old url of an article to the article
+pub fn redirect_page(target: &url::Url) -> String {
+    html! {
+        <>
+            <HTML5Doctype />
+            <html>
+                <head>
+                    <title>{"Redirecting..."}</title>
+                    <meta name={"robots"} content={"noindex"} />
+                    <meta http-equiv={"refresh"} content={format!("0; url={}", target)} />
+                    <link rel={"canonical"} href={target.to_string()} />
+                </head>
+                <body>
+                    <a href={target.to_string()}>{target.to_string()}</a>
+                </body>
+            </html>
+        </>
+    }
+}
+
 #[cfg(test)]
 mod test_layout {
-    use super::{Heading, Html};
+    use super::{article_page, index_path, long_toc, website_og, Heading, Html};
+    use crate::config::SiteConfig;
+    use crate::db::models::Article;
     use render::html;
 
     #[test]
     fn test() {
+        let site = SiteConfig::default();
         let rendered_html = html! {
-            <Html title={"Main page"} description={"Foolio!"}>
+            <Html
+                title={"Main page"}
+                description={"Foolio!"}
+                noindex={false}
+                og={website_og("Main page", "/", &site)}
+                site={&site}>
                 <Heading title={"Hello world!".into()} fullname={"Foo fighters".into()} />
             </Html>
         };
         println!("html?: {:?}", rendered_html);
     }
+
+    #[test]
+    fn test_index_path() {
+        assert_eq!(index_path(1), "/");
+        assert_eq!(index_path(2), "/page/2");
+    }
+
+    #[test]
+    fn test_long_toc() {
+        let toc = r#"[
+            {"level": 2, "id": "a", "title": "A", "children": [
+                {"level": 3, "id": "b", "title": "B", "children": []}
+            ]},
+            {"level": 2, "id": "c", "title": "C", "children": []}
+        ]"#;
+        let mut site = SiteConfig::default();
+        assert!(long_toc(toc, "", &site)
+            .unwrap()
+            .starts_with("<ul><li><a href=\"#a\">"));
+
+        // The document has the table of contents in place of `[TOC]`
+        let html = "<nav class=\"toc\"><ul></ul></nav>\n<h2 id=\"a\">";
+        assert_eq!(long_toc(toc, html, &site), None);
+        assert_eq!(long_toc("[{", "", &site), None);
+
+        site.toc_min_headings = 4;
+        assert_eq!(long_toc(toc, "", &site), None);
+        site.toc_min_headings = 0;
+        assert_eq!(long_toc(toc, "", &site), None);
+        assert_eq!(long_toc("", "", &SiteConfig::default()), None);
+    }
+
+    #[test]
+    fn test_article_author() {
+        let article = Article::test("/articles/post01");
+        let mut site = SiteConfig::default();
+        assert!(!article_page(&article, None, &site).contains("article:author"));
+
+        site.author = "John Doe".into();
+        assert!(article_page(&article, None, &site).contains("content=\"John Doe\""));
+    }
 }
@@ -4,21 +4,42 @@ extern crate diesel;
 #[macro_use]
 extern crate diesel_migrations;
 
+mod cli;
+mod config;
 mod db;
+mod feeds;
+mod fingerprint;
 mod generate_db;
+mod generate_html;
 mod git;
+mod images;
 mod layout;
 mod markdown;
+mod new_article;
 mod normalize;
+mod redirects;
+mod resources;
+mod search;
+mod serve;
+mod sitemap;
+mod slug;
 mod urls;
+mod watch;
 
 use crate::db::models::Article;
 use async_std::path::PathBuf;
 use async_std::{channel::unbounded, task::JoinHandle};
-use db::DbConnection;
+use chrono::TimeZone;
+use cli::{BuildOpts, Command};
+use config::{load_config, SiteConfig};
+use db::{DbConnection, Published};
 use derive_more::From;
-use generate_db::sync;
+use generate_db::{sync, sync_changes};
+use generate_html::{check_links, generate_html};
+use new_article::new_article;
 use normalize::normalize;
+use serve::{serve, LiveReload};
+use structopt::StructOpt;
 
 #[derive(Clone)]
 pub struct GenerateParams {
@@ -28,11 +49,29 @@ pub struct GenerateParams {
     pub root_dir: PathBuf,
     pub db_file: PathBuf,
     pub clean_output: bool,
+    pub watch: bool,
+
+    /// Compiles and checks the documents without writing the output
+    pub check: bool,
+
+    /// Publishes the draft articles too
+    pub drafts: bool,
+
+    /// Address of the preview server, e.g. `127.0.0.1:8080`
+    pub serve_address: Option<String>,
+
+    pub site: SiteConfig,
 }
 
-// async fn generate_resources_db(article: &Article) -> Result<(), DbCreationError> {
-//     todo!()
-// }
+impl GenerateParams {
+    /// Articles published in the output, future dated articles are scheduled
+    pub fn published(&self) -> Published {
+        Published {
+            drafts: self.drafts,
+            until: chrono::Utc::now().naive_utc(),
+        }
+    }
+}
 
 // async fn generate_images_db(article: &Article) -> Result<(), DbCreationError> {
 //     todo!()
@@ -89,6 +128,29 @@ pub struct GenerateParams {
 enum GenerateError {
     DbError(crate::db::Error),
     PatternError(glob::PatternError),
+    GenerateDbError(generate_db::Error),
+    HtmlError(generate_html::Error),
+    WatchError(watch::Error),
+    ServeError(serve::Error),
+
+    /// Number of documents that failed to compile or have broken links
+    DocumentErrors(usize),
+}
+
+/// Prints the future dated articles, a build after their publishing time
+/// publishes them
+async fn report_scheduled(
+    params: &GenerateParams,
+    pool: &DbConnection,
+) -> Result<(), GenerateError> {
+    for article in Article::get_scheduled(&pool, params.published()).await? {
+        println!(
+            "Scheduled {} for {}",
+            article.local_path,
+            chrono::Utc.from_utc_datetime(&article.published)
+        );
+    }
+    Ok(())
 }
 
 async fn generate(params: &GenerateParams) -> Result<(), GenerateError> {
@@ -100,6 +162,20 @@ async fn generate(params: &GenerateParams) -> Result<(), GenerateError> {
     let (sender, receiver) = unbounded();
 
     let mut generate_db_task: Option<JoinHandle<()>> = None;
+    let mut watch_task: Option<JoinHandle<()>> = None;
+    let mut params = params.clone();
+
+    // Documents failed since the previous generation
+    let mut errors = 0;
+
+    // Changes made while generating, they are generated after it
+    let mut queued_changes: Vec<FilesChange> = vec![];
+
+    let live_reload = LiveReload::new();
+    let _serve_task = match &params.serve_address {
+        Some(address) => Some(serve(&address, &params.output_dir, &live_reload).await?),
+        None => None,
+    };
 
     // Initially, run Sync
     let _ = sender.send(Message::Sync).await;
@@ -111,12 +187,65 @@ async fn generate(params: &GenerateParams) -> Result<(), GenerateError> {
                     if let Some(thread) = generate_db_task {
                         thread.cancel().await;
                     }
-                    generate_db_task = Some(sync(&params, &pool, &sender).await.unwrap());
+                    queued_changes.clear();
+                    generate_db_task = Some(sync(&params, &pool, &sender).await?);
                 }
-                Message::DbGenerated => {
-                    println!("Done!");
+                Message::Changes(changes) if generate_db_task.is_some() => {
+                    queued_changes.extend(changes);
+                }
+                Message::Changes(changes) => {
+                    generate_db_task = Some(sync_changes(changes, &params, &pool, &sender).await?);
+                }
+                Message::DbArticleError { path, error } | Message::DbPageError { path, error } => {
+                    println!("{}: {:?}", path.display(), error);
+                    errors += 1;
+                }
+                Message::DbGenerated if params.check => {
+                    report_scheduled(&params, &pool).await?;
+                    let broken = check_links(&params, &pool).await?;
+                    for error in &broken {
+                        println!("{:?}", error);
+                    }
+                    errors += broken.len();
+                    if errors > 0 {
+                        return Err(GenerateError::DocumentErrors(errors));
+                    }
+                    println!("No errors found");
                     break;
                 }
+                Message::DbGenerated => {
+                    generate_db_task = None;
+                    report_scheduled(&params, &pool).await?;
+                    let generated = generate_html(&params, &pool).await;
+                    if !params.watch {
+                        generated?;
+                        if errors > 0 {
+                            return Err(GenerateError::DocumentErrors(errors));
+                        }
+                        println!("Done!");
+                        break;
+                    }
+                    errors = 0;
+
+                    // While watching, errors are fixed by editing the files
+                    match generated {
+                        Ok(()) => println!("Done!"),
+                        Err(error) => println!("{:?}", error),
+                    }
+                    live_reload.reload().await;
+
+                    // Output is cleaned only before the first generation
+                    params.clean_output = false;
+                    if watch_task.is_none() {
+                        watch_task = Some(watch::watch(&params, &sender)?);
+                        println!("Watching for changes...");
+                    }
+                    if !queued_changes.is_empty() {
+                        let changes = std::mem::take(&mut queued_changes);
+                        generate_db_task =
+                            Some(sync_changes(changes, &params, &pool, &sender).await?);
+                    }
+                }
                 m => {
                     println!("{:?}", m);
                     //
@@ -134,9 +263,41 @@ async fn generate(params: &GenerateParams) -> Result<(), GenerateError> {
     Ok(())
 }
 
-#[derive(Debug)]
+#[derive(From, Debug)]
 enum MainError {
-    IOError,
+    GenerateError(GenerateError),
+    NewArticleError(new_article::Error),
+    ConfigError(config::Error),
+}
+
+/// Parameters from the flags, the site configuration and the defaults, in
+/// that order
+async fn generate_params(opts: BuildOpts) -> Result<GenerateParams, MainError> {
+    let root_dir: PathBuf = opts.root_dir.into();
+    let site = load_config(&root_dir).await?;
+    let dir = |flag: Option<std::path::PathBuf>,
+               configured: &Option<std::path::PathBuf>,
+               default: &str| {
+        match (flag, configured) {
+            (Some(flag), _) => flag.into(),
+            (None, Some(configured)) => root_dir.join(configured),
+            (None, None) => root_dir.join(default),
+        }
+    };
+
+    Ok(GenerateParams {
+        article_dir: dir(opts.article_dir, &site.article_dir, "articles"),
+        pages_dir: dir(opts.pages_dir, &site.pages_dir, "pages"),
+        output_dir: dir(opts.output_dir, &site.output_dir, ".out"),
+        db_file: dir(opts.db_file, &site.db_file, ".cache.db"),
+        root_dir: root_dir.clone(),
+        clean_output: !opts.no_clean_output,
+        watch: false,
+        check: false,
+        drafts: opts.drafts,
+        serve_address: None,
+        site,
+    })
 }
 
 #[derive(Debug)]
@@ -160,19 +321,47 @@ pub enum Message {
         path: PathBuf,
         urls: Vec<url::Url>,
     },
+    DbArticleUnchanged {
+        path: PathBuf,
+    },
+    DbPageError {
+        path: PathBuf,
+        error: generate_db::Error,
+    },
+    DbPageCreated {
+        path: PathBuf,
+        urls: Vec<url::Url>,
+    },
+    DbPageUnchanged {
+        path: PathBuf,
+    },
     DbGenerated,
 }
 
 #[async_std::main]
 async fn main() -> Result<(), MainError> {
-    let _ = generate(&GenerateParams {
-        article_dir: ".\\examples\\articles".into(),
-        pages_dir: ".\\examples\\pages".into(),
-        clean_output: true,
-        db_file: ".cache.db".into(),
-        output_dir: ".\\.out".into(),
-        root_dir: ".".into(),
-    })
-    .await;
+    let params = match Command::from_args() {
+        Command::Build(opts) => generate_params(opts).await?,
+        Command::Watch(opts) => GenerateParams {
+            watch: true,
+            ..generate_params(opts).await?
+        },
+        Command::Serve { opts, address } => GenerateParams {
+            watch: true,
+            serve_address: Some(address),
+            ..generate_params(opts).await?
+        },
+        Command::Check(opts) => GenerateParams {
+            check: true,
+            ..generate_params(opts).await?
+        },
+        Command::New { opts, title } => {
+            let params = generate_params(opts).await?;
+            let file = new_article(&params.article_dir, &title).await?;
+            println!("Created {}", file.display());
+            return Ok(());
+        }
+    };
+    generate(&params).await?;
     Ok(())
 }
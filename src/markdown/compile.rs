@@ -2,13 +2,15 @@ use async_std::{fs, path::PathBuf};
 use chrono::{DateTime, NaiveDateTime, Utc};
 use derive_more::From;
 
-use crate::{git, normalize::normalize};
+use crate::{fingerprint::document_hash, git, normalize::normalize};
 
-use super::{frontmatter, to_html::markdown_to_html};
+use super::excerpt::{truncate_words, DESCRIPTION_LENGTH};
+use super::{frontmatter, to_html::markdown_to_html, toc::TocHeading};
 
 #[derive(Debug, From)]
 pub enum Error {
     FileNameError,
+    InvalidSlug(String),
     GitError(git::Error),
     FrontmatterParseError(frontmatter::Error),
     IOError(std::io::Error),
@@ -24,13 +26,29 @@ pub struct CompiledMarkdown {
     pub local_path: PathBuf,
     pub old_url: Option<url::Url>,
     pub html: String,
+
+    /// Headings of the document
+    pub toc: Vec<TocHeading>,
+
+    /// Plain text summary, from the frontmatter or the first paragraph
+    pub description: String,
+    pub word_count: usize,
+
+    /// Estimated reading time in minutes
+    pub reading_time: usize,
+    pub hash: String,
+    pub noindex: bool,
+    pub categories: Vec<String>,
+    pub tags: Vec<String>,
+    pub draft: bool,
 }
 
 pub async fn compile_markdown_file(path: &PathBuf) -> Result<CompiledMarkdown, Error> {
     let path = normalize(path).await?;
     let content = fs::read_to_string(&path).await?;
+    let hash = document_hash(content.as_bytes());
     let metadata = fs::metadata(&path).await?;
-    let modified_on_disk = metadata.modified()?.into();
+    let modified_on_disk: DateTime<Utc> = metadata.modified()?.into();
     let (frontmatter, markdown_all) = frontmatter::get_frontmatter(&content)?;
     let (title, markdown): (String, String) = match frontmatter.title {
         None => {
@@ -43,31 +61,98 @@ pub async fn compile_markdown_file(path: &PathBuf) -> Result<CompiledMarkdown, E
         }
         Some(title) => (title, markdown_all.to_owned()),
     };
-    let html = markdown_to_html(&markdown).await;
+    let converted = markdown_to_html(&markdown).await;
+    let description = frontmatter
+        .description
+        .unwrap_or_else(|| truncate_words(&converted.first_paragraph, DESCRIPTION_LENGTH));
     let published = match frontmatter.published {
         Some(f) => f,
-        None => git::git_added(&path).await?,
+        None => or_modified_on_disk(git::git_added(&path).await, modified_on_disk)?,
     };
-    let modified = git::git_modified(&path).await?;
-    let slug = path
-        .file_name()
-        .ok_or(Error::FileNameError)?
-        .to_string_lossy()
-        .trim_end_matches(".md")
-        .into();
+    let modified = or_modified_on_disk(git::git_modified(&path).await, modified_on_disk)?;
+    let slug = match frontmatter.slug {
+        Some(slug) => slug,
+        None => path
+            .file_name()
+            .ok_or(Error::FileNameError)?
+            .to_string_lossy()
+            .trim_end_matches(".md")
+            .into(),
+    };
+    if !is_valid_slug(&slug) {
+        return Err(Error::InvalidSlug(slug));
+    }
 
     Ok(CompiledMarkdown {
         slug,
         title,
         old_url: frontmatter.old_url,
+        noindex: frontmatter.noindex,
+        categories: frontmatter.categories,
+        tags: frontmatter.tags,
+        draft: frontmatter.draft,
         modified,
         modified_on_disk,
         local_path: path,
         published,
-        html,
+        html: converted.html,
+        toc: converted.toc,
+        description,
+        word_count: converted.word_count,
+        reading_time: converted.reading_time,
+        hash,
     })
 }
 
+/// Date from git, or the modification time of the file when it's not
+/// committed yet, e.g. drafts
+fn or_modified_on_disk(
+    date: Result<DateTime<Utc>, git::Error>,
+    modified_on_disk: DateTime<Utc>,
+) -> Result<DateTime<Utc>, Error> {
+    match date {
+        Err(git::Error::NoHistory) => Ok(modified_on_disk),
+        date => Ok(date?),
+    }
+}
+
+/// Are the `/` separated segments of the slug made of unreserved url
+/// characters, other than `.` and `..`
+fn is_valid_slug(slug: &str) -> bool {
+    slug.split('/').all(|segment| {
+        !segment.is_empty()
+            && segment != "."
+            && segment != ".."
+            && segment
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "-._~".contains(c))
+    })
+}
+
+#[cfg(test)]
+mod test_slug {
+    use super::is_valid_slug;
+
+    #[test]
+    fn test_is_valid_slug() {
+        assert!(is_valid_slug("post01"));
+        assert!(is_valid_slug("release-1.0"));
+        assert!(is_valid_slug("2021/post01"));
+        assert!(!is_valid_slug(""));
+        assert!(!is_valid_slug("."));
+        assert!(!is_valid_slug(".."));
+        assert!(!is_valid_slug("../secret"));
+        assert!(!is_valid_slug("a/../../x"));
+        assert!(!is_valid_slug("/post01"));
+        assert!(!is_valid_slug("post01/"));
+        assert!(!is_valid_slug("2021\\post01"));
+        assert!(!is_valid_slug("post?draft=1"));
+        assert!(!is_valid_slug("post#intro"));
+        assert!(!is_valid_slug("first post"));
+        assert!(!is_valid_slug("post\n01"));
+    }
+}
+
 #[cfg(test)]
 mod test_compile_markdown {
     use super::compile_markdown_file;
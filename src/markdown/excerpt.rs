@@ -0,0 +1,135 @@
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use super::to_html::strip_heading_anchors;
+
+/// Length of the descriptions taken from the first paragraph, in characters
+pub const DESCRIPTION_LENGTH: usize = 160;
+
+/// Comments and tags of the html, the tag name is captured with the slash of
+/// a closing tag
+static TAG: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"<!--(?s:.*?)-->|<(/?)([a-zA-Z][a-zA-Z0-9]*)[^>]*>").unwrap());
+static MORE: Lazy<Regex> = Lazy::new(|| Regex::new(r"^<!--\s*more\s*-->$").unwrap());
+
+/// Elements without a closing tag
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track",
+    "wbr",
+];
+
+/// Html before the `<!-- more -->` marker, or the first paragraph if there is
+/// no marker, without the heading anchors
+///
+/// Only the marker and paragraphs outside of other elements are used, so the
+/// excerpt never cuts a list or a quote in half.
+pub fn excerpt(html: &str) -> String {
+    strip_heading_anchors(excerpt_html(html))
+}
+
+fn excerpt_html(html: &str) -> &str {
+    let mut depth = 0usize;
+    let mut paragraph_start = None;
+    let mut first_paragraph = None;
+    for tag in TAG.captures_iter(html) {
+        let matched = tag.get(0).unwrap();
+        let name = match tag.get(2) {
+            Some(name) => name.as_str().to_lowercase(),
+            None if depth == 0 && MORE.is_match(matched.as_str()) => {
+                return html[..matched.start()].trim_end();
+            }
+            None => continue,
+        };
+        if VOID_ELEMENTS.contains(&name.as_str()) || matched.as_str().ends_with("/>") {
+            continue;
+        }
+
+        if &tag[1] == "/" {
+            depth = depth.saturating_sub(1);
+            if depth == 0 && name == "p" && first_paragraph.is_none() {
+                if let Some(start) = paragraph_start {
+                    first_paragraph = Some(&html[start..matched.end()]);
+                }
+            }
+        } else {
+            if depth == 0 && name == "p" && paragraph_start.is_none() {
+                paragraph_start = Some(matched.start());
+            }
+            depth += 1;
+        }
+    }
+    first_paragraph.unwrap_or("")
+}
+
+/// Text shortened to at most `max_chars` characters at a word boundary, with
+/// an ellipsis if it was shortened
+pub fn truncate_words(text: &str, max_chars: usize) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    let end = match text.char_indices().nth(max_chars) {
+        Some(_) => {
+            text.char_indices()
+                .nth(max_chars.saturating_sub(1))
+                .unwrap()
+                .0
+        }
+        None => return text,
+    };
+
+    // The last word is left out, unless the text is cut right after it or
+    // there is only one word
+    let cut = if text[end..].starts_with(' ') {
+        &text[..end]
+    } else {
+        text[..end]
+            .rfind(' ')
+            .map_or(&text[..end], |space| &text[..space])
+    };
+    format!("{}…", cut.trim_end_matches(|c: char| !c.is_alphanumeric()))
+}
+
+#[cfg(test)]
+mod test_excerpt {
+    use super::{excerpt, truncate_words};
+
+    #[test]
+    fn test_excerpt() {
+        assert_eq!(
+            excerpt("<h2>Intro</h2>\n<p>First</p>\n<p>Second</p>"),
+            "<p>First</p>"
+        );
+        assert_eq!(excerpt("<h2>Intro</h2>"), "");
+        assert_eq!(
+            excerpt("<p>First</p>\n<p>Second</p>\n<!-- more -->\n<p>Third</p>"),
+            "<p>First</p>\n<p>Second</p>"
+        );
+        assert_eq!(excerpt("<p>First</p>\n<!--more-->"), "<p>First</p>");
+    }
+
+    #[test]
+    fn test_excerpt_top_level() {
+        assert_eq!(
+            excerpt("<blockquote>\n<p>Quote</p>\n</blockquote>\n<p>First<br />line</p>"),
+            "<p>First<br />line</p>"
+        );
+        assert_eq!(
+            excerpt("<ul>\n<li>One <!-- more --></li>\n</ul>\n<p>First</p>\n<!-- more -->"),
+            "<ul>\n<li>One <!-- more --></li>\n</ul>\n<p>First</p>"
+        );
+        assert_eq!(
+            excerpt(concat!(
+                r##"<h2 id="intro"><a class="anchor" href="#intro" aria-hidden="true">#</a>Intro</h2>"##,
+                "\n<p>First</p>\n<!-- more -->"
+            )),
+            "<h2 id=\"intro\">Intro</h2>\n<p>First</p>"
+        );
+    }
+
+    #[test]
+    fn test_truncate_words() {
+        assert_eq!(truncate_words(" Short\n text ", 10), "Short text");
+        assert_eq!(truncate_words("Hello world, again", 14), "Hello world…");
+        assert_eq!(truncate_words("Hello world again", 12), "Hello world…");
+        assert_eq!(truncate_words("Äänekoski on kaupunki", 13), "Äänekoski on…");
+        assert_eq!(truncate_words("Supercalifragilistic", 5), "Supe…");
+    }
+}
@@ -11,8 +11,26 @@ pub enum Error {
 #[derive(Debug, Eq, PartialEq, Deserialize, Default)]
 pub struct Frontmatter {
     pub title: Option<String>,
+    pub slug: Option<String>,
     pub published: Option<DateTime<Utc>>,
     pub old_url: Option<url::Url>,
+
+    /// Summary in the meta tags and feeds, the first paragraph by default
+    pub description: Option<String>,
+
+    /// Hides the document from search engines
+    #[serde(default)]
+    pub noindex: bool,
+
+    #[serde(default)]
+    pub categories: Vec<String>,
+
+    #[serde(default)]
+    pub tags: Vec<String>,
+
+    /// Leaves the article out of the output, unless drafts are published
+    #[serde(default)]
+    pub draft: bool,
 }
 
 pub fn get_frontmatter(markdown: &str) -> Result<(Frontmatter, String), Error> {
@@ -47,6 +65,7 @@ mod test_frontmatter {
             published: 2020-01-01 12:00:00 +03:00
             title: First post
             old_url: https://www.foo.com/path1/path2
+            description: All about the first post
             ---
 
             # The title
@@ -61,6 +80,12 @@ mod test_frontmatter {
             published: Utc.ymd(2020, 1, 1).and_hms(9, 0, 0).into(),
             old_url: Some(Url::parse("https://www.foo.com/path1/path2").unwrap()),
             title: Some("First post".into()),
+            slug: None,
+            description: Some("All about the first post".into()),
+            noindex: false,
+            categories: vec![],
+            tags: vec![],
+            draft: false,
         };
 
         assert_eq!(frontmatter, expected_frontmatter);
@@ -74,4 +99,36 @@ mod test_frontmatter {
             .replace("            ", "")
         )
     }
+
+    #[test]
+    fn test_frontmatter_slug() {
+        let (frontmatter, _) = get_frontmatter(
+            &"---
+            slug: first-post-2021
+            ---
+            "
+            .replace("            ", ""),
+        )
+        .unwrap();
+
+        assert_eq!(frontmatter.slug, Some("first-post-2021".into()));
+    }
+
+    #[test]
+    fn test_frontmatter_terms() {
+        let (frontmatter, _) = get_frontmatter(
+            &"---
+            categories:
+                - Foo
+                - Too
+            tags: [rust, diesel]
+            ---
+            "
+            .replace("            ", ""),
+        )
+        .unwrap();
+
+        assert_eq!(frontmatter.categories, vec!["Foo", "Too"]);
+        assert_eq!(frontmatter.tags, vec!["rust", "diesel"]);
+    }
 }
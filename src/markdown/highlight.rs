@@ -0,0 +1,250 @@
+use derive_more::From;
+use once_cell::sync::Lazy;
+use pulldown_cmark::escape::escape_html;
+use syntect::highlighting::{Color, ThemeSet};
+use syntect::html::{css_for_theme_with_class_style, line_tokens_to_classed_spans, ClassStyle};
+use syntect::parsing::{ParseState, Scope, ScopeStack, SyntaxReference, SyntaxSet};
+use syntect::util::LinesWithEndings;
+
+/// Server path of the stylesheet of the highlighted code
+pub const SYNTAX_CSS_PATH: &str = "/syntax.css";
+
+/// Prefix of the scope classes, so they don't collide with the classes of
+/// the layout
+const CLASS_PREFIX: &str = "hl-";
+
+static SYNTAXES: Lazy<SyntaxSet> = Lazy::new(SyntaxSet::load_defaults_newlines);
+static THEMES: Lazy<ThemeSet> = Lazy::new(ThemeSet::load_defaults);
+
+#[derive(Debug, From)]
+pub enum Error {
+    UnknownTheme(String),
+    SyntectError(syntect::Error),
+}
+
+/// Attributes of the fenced code block, from an info string like
+/// `rust,linenos,hl_lines=1 3-5`
+#[derive(Debug, Default, PartialEq)]
+struct CodeInfo {
+    lang: String,
+    linenos: bool,
+
+    /// Inclusive ranges of the highlighted line numbers
+    hl_lines: Vec<(usize, usize)>,
+}
+
+impl CodeInfo {
+    fn parse(info: &str) -> Self {
+        let mut parts = info.split(',').map(str::trim);
+        let mut code_info = CodeInfo {
+            lang: parts.next().unwrap_or("").into(),
+            ..Default::default()
+        };
+        for part in parts {
+            let mut attr = part.splitn(2, '=').map(str::trim);
+            match (attr.next(), attr.next()) {
+                (Some("linenos"), None) => code_info.linenos = true,
+                (Some("hl_lines"), Some(ranges)) => {
+                    code_info.hl_lines = ranges.split_whitespace().filter_map(parse_range).collect()
+                }
+                _ => (),
+            }
+        }
+        code_info
+    }
+
+    fn is_highlighted(&self, line: usize) -> bool {
+        self.hl_lines
+            .iter()
+            .any(|&(start, end)| start <= line && line <= end)
+    }
+}
+
+/// Parses a line number like `3` or a range like `3-5`
+fn parse_range(range: &str) -> Option<(usize, usize)> {
+    let mut bounds = range.splitn(2, '-');
+    let start = bounds.next()?.parse().ok()?;
+    let end = match bounds.next() {
+        Some(end) => end.parse().ok()?,
+        None => start,
+    };
+    Some((start, end))
+}
+
+/// Classes of the scope, e.g. `hl-keyword hl-control hl-rust`
+fn scope_classes(scope: Scope) -> String {
+    scope
+        .build_string()
+        .split('.')
+        .map(|atom| format!("{}{}", CLASS_PREFIX, atom))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Html of each line without the newline, the spans still open at the end of
+/// a line are closed and reopened on the next line
+fn highlight_lines(code: &str, syntax: &SyntaxReference) -> Result<Vec<String>, syntect::Error> {
+    let mut state = ParseState::new(syntax);
+    let mut stack = ScopeStack::new();
+    let mut lines = vec![];
+    for line in LinesWithEndings::from(code) {
+        let mut html = String::new();
+        for scope in &stack.scopes {
+            html.push_str(&format!("<span class=\"{}\">", scope_classes(*scope)));
+        }
+        let ops = state.parse_line(line, &SYNTAXES)?;
+        let (line_html, _) = line_tokens_to_classed_spans(
+            line,
+            &ops,
+            ClassStyle::SpacedPrefixed {
+                prefix: CLASS_PREFIX,
+            },
+            &mut stack,
+        )?;
+        html.push_str(&line_html.replace('\n', ""));
+        html.push_str(&"</span>".repeat(stack.scopes.len()));
+        lines.push(html);
+    }
+    Ok(lines)
+}
+
+fn plain_lines(code: &str) -> Vec<String> {
+    code.lines()
+        .map(|line| {
+            let mut html = String::new();
+            escape_html(&mut html, line).unwrap();
+            html
+        })
+        .collect()
+}
+
+/// Html of the fenced code block, highlighted by the language of the info
+/// string. Code of unknown languages is escaped as is.
+pub fn highlight_code(code: &str, info: &str) -> String {
+    let info = CodeInfo::parse(info);
+    let lines = SYNTAXES
+        .find_syntax_by_token(&info.lang)
+        .filter(|_| !info.lang.is_empty())
+        .and_then(|syntax| highlight_lines(code, syntax).ok())
+        .unwrap_or_else(|| plain_lines(code));
+
+    let mut html = format!("<pre class=\"{}code\"><code", CLASS_PREFIX);
+    if !info.lang.is_empty() {
+        html.push_str(" class=\"language-");
+        escape_html(&mut html, &info.lang).unwrap();
+        html.push('"');
+    }
+    html.push('>');
+    for (i, line) in lines.iter().enumerate() {
+        let number = i + 1;
+        if info.is_highlighted(number) {
+            html.push_str("<span class=\"line highlight\">");
+        } else {
+            html.push_str("<span class=\"line\">");
+        }
+        if info.linenos {
+            html.push_str(&format!("<span class=\"lineno\">{}</span>", number));
+        }
+        html.push_str(line);
+        html.push_str("\n</span>");
+    }
+    html.push_str("</code></pre>\n");
+    html
+}
+
+fn css_color(color: Color) -> String {
+    format!("#{:02x}{:02x}{:02x}", color.r, color.g, color.b)
+}
+
+/// Stylesheet of the highlighted code in the colors of the theme, e.g.
+/// `InspiredGitHub` or `base16-ocean.dark`
+pub fn theme_css(name: &str) -> Result<String, Error> {
+    let theme = THEMES
+        .themes
+        .get(name)
+        .ok_or_else(|| Error::UnknownTheme(name.into()))?;
+    let mut css = css_for_theme_with_class_style(
+        theme,
+        ClassStyle::SpacedPrefixed {
+            prefix: CLASS_PREFIX,
+        },
+    )?;
+
+    let highlight = theme
+        .settings
+        .line_highlight
+        .map(css_color)
+        .unwrap_or_else(|| "rgba(255, 255, 0, 0.2)".into());
+    css.push_str(&format!(
+        ".{}code .line.highlight {{\n background-color: {};\n}}\n",
+        CLASS_PREFIX, highlight
+    ));
+
+    css.push_str(&format!(
+        ".{}code .lineno {{\n display: inline-block;\n min-width: 2em;\n margin-right: 1em;\n text-align: right;\n user-select: none;\n",
+        CLASS_PREFIX
+    ));
+    if let Some(gutter) = theme.settings.gutter_foreground {
+        css.push_str(&format!(" color: {};\n", css_color(gutter)));
+    }
+    css.push_str("}\n");
+    Ok(css)
+}
+
+#[cfg(test)]
+mod test_highlight {
+    use super::{highlight_code, theme_css, CodeInfo};
+
+    #[test]
+    fn test_parse_info() {
+        assert_eq!(
+            CodeInfo::parse("rust, linenos, hl_lines=1 3-5 x"),
+            CodeInfo {
+                lang: "rust".into(),
+                linenos: true,
+                hl_lines: vec![(1, 1), (3, 5)],
+            }
+        );
+        assert_eq!(CodeInfo::parse(""), CodeInfo::default());
+    }
+
+    #[test]
+    fn test_highlight_code() {
+        let html = highlight_code("let x = \"<a>\";\n", "rust");
+        assert!(html.starts_with("<pre class=\"hl-code\"><code class=\"language-rust\">"));
+        assert!(html.contains("<span class=\"hl-storage hl-type hl-rust\">let</span>"));
+        assert!(html.contains("&lt;a&gt;"));
+        assert!(html.ends_with("\n</span></code></pre>\n"));
+    }
+
+    #[test]
+    fn test_highlight_multiline_scope() {
+        // The comment spans are closed at the end of each line
+        let html = highlight_code("/* a\nb */\n", "rust");
+        let lines: Vec<&str> = html.split("<span class=\"line\">").skip(1).collect();
+        assert_eq!(lines.len(), 2);
+        for line in lines {
+            assert_eq!(
+                line.matches("<span").count(),
+                line.matches("</span>").count() - 1
+            );
+            assert!(line.contains("hl-comment"));
+        }
+    }
+
+    #[test]
+    fn test_highlight_unknown_language() {
+        assert_eq!(
+            highlight_code("<b>\n", "nosuchlang,linenos,hl_lines=1"),
+            "<pre class=\"hl-code\"><code class=\"language-nosuchlang\">\
+             <span class=\"line highlight\"><span class=\"lineno\">1</span>&lt;b&gt;\n</span>\
+             </code></pre>\n"
+        );
+    }
+
+    #[test]
+    fn test_theme_css() {
+        assert!(theme_css("InspiredGitHub").unwrap().contains(".hl-code {"));
+        assert!(theme_css("no such theme").is_err());
+    }
+}
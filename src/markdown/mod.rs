@@ -1,6 +1,18 @@
 mod compile;
+mod excerpt;
 mod frontmatter;
+mod highlight;
 mod to_html;
+mod toc;
 
 pub use compile::compile_markdown_file;
+pub use compile::CompiledMarkdown;
 pub use compile::Error;
+pub use excerpt::excerpt;
+pub use highlight::theme_css;
+pub use highlight::Error as HighlightError;
+pub use highlight::SYNTAX_CSS_PATH;
+pub use to_html::strip_heading_anchors;
+pub use toc::toc_html;
+pub use toc::TocHeading;
+pub use toc::INLINE_TOC_START;
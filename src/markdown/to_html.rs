@@ -1,72 +1,239 @@
-use pulldown_cmark::{escape, html, CodeBlockKind, Event, LinkType, Options, Parser, Tag};
+use once_cell::sync::Lazy;
+use pulldown_cmark::escape::{escape_href, escape_html};
+use pulldown_cmark::{html, CodeBlockKind, Event, LinkType, Options, Parser, Tag};
+use regex::Regex;
+
+use std::collections::HashSet;
+
+use super::highlight::highlight_code;
+use super::toc::{heading_tree, toc_html, TocHeading, INLINE_TOC_START};
+use crate::slug::slugify;
+
+/// Placeholder paragraph replaced with the table of contents
+const TOC_PLACEHOLDER: &str = "<p>[TOC]</p>";
+
+/// Anchor link added to the start of the headings
+static HEADING_ANCHOR: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r##"<a class="anchor" href="#[^"]*" aria-hidden="true">#</a>"##).unwrap()
+});
+
+/// Average reading speed, in words per minute
+const WORDS_PER_MINUTE: usize = 200;
 
 struct EventIter<'a> {
     p: Parser<'a>,
+
+    /// Headings in document order
+    headings: Vec<TocHeading>,
+    ids: HashSet<String>,
+
+    /// Plain text of the paragraph being read, until the first paragraph
+    /// with text is found
+    paragraph: Option<String>,
+    first_paragraph: Option<String>,
+
+    /// Number of lists, quotes and footnotes being read, only the paragraphs
+    /// outside of them are first paragraphs like in the excerpts
+    depth: usize,
+
+    /// Words of the text, code and html are not counted
+    word_count: usize,
+    in_word: bool,
 }
 
 impl<'a> EventIter<'a> {
     pub fn new(p: Parser<'a>) -> Self {
-        EventIter { p }
+        EventIter {
+            p,
+            headings: vec![],
+            ids: HashSet::new(),
+            paragraph: None,
+            first_paragraph: None,
+            depth: 0,
+            word_count: 0,
+            in_word: false,
+        }
+    }
+
+    /// Counts the words of the text, a word may continue from the previous
+    /// text
+    fn count_words(&mut self, text: &str) {
+        for c in text.chars() {
+            if c.is_whitespace() {
+                self.in_word = false;
+            } else if !self.in_word {
+                self.in_word = true;
+                self.word_count += 1;
+            }
+        }
+    }
+
+    /// Slug of the heading title, numbered if the slug is already taken
+    fn unique_id(&mut self, title: &str) -> String {
+        let slug = match slugify(title) {
+            slug if slug.is_empty() => "section".into(),
+            slug => slug,
+        };
+        let mut id = slug.clone();
+        let mut number = 1;
+        while !self.ids.insert(id.clone()) {
+            id = format!("{}-{}", slug, number);
+            number += 1;
+        }
+        id
+    }
+
+    /// Consumes the events inside the heading tag, and returns the heading
+    /// with an id and a link to itself
+    fn heading(&mut self, level: u32) -> String {
+        let mut events = vec![];
+        let mut title = String::new();
+        while let Some(event) = self.next() {
+            match &event {
+                Event::End(Tag::Heading(_)) => break,
+                Event::Text(text) | Event::Code(text) => title.push_str(text),
+                _ => (),
+            }
+            events.push(event);
+        }
+        let id = self.unique_id(&title);
+
+        let mut html = format!("<h{} id=\"", level);
+        escape_html(&mut html, &id).unwrap();
+        html.push_str("\"><a class=\"anchor\" href=\"#");
+        escape_html(&mut html, &id).unwrap();
+        html.push_str("\" aria-hidden=\"true\">#</a>");
+        html::push_html(&mut html, events.into_iter());
+        html.push_str(&format!("</h{}>\n", level));
+
+        self.headings.push(TocHeading {
+            level,
+            id,
+            title: title.trim().into(),
+            children: vec![],
+        });
+        html
+    }
+
+    /// Consumes the events inside the image tag as a plain text
+    fn alt_text(&mut self) -> String {
+        let mut alt = String::new();
+        let mut nest = 0;
+        for event in &mut self.p {
+            match event {
+                Event::Start(_) => nest += 1,
+                Event::End(_) => {
+                    if nest == 0 {
+                        break;
+                    }
+                    nest -= 1;
+                }
+                Event::Text(text) | Event::Code(text) => alt.push_str(&text),
+                Event::SoftBreak | Event::HardBreak => alt.push(' '),
+                _ => (),
+            }
+        }
+        alt
+    }
+
+    /// Consumes the text of the code block
+    fn code(&mut self) -> String {
+        let mut code = String::new();
+        for event in &mut self.p {
+            match event {
+                Event::Text(text) => code.push_str(&text),
+                _ => break,
+            }
+        }
+        code
     }
 }
 
+/// Is the tag a block containing other blocks
+fn is_container(tag: &Tag) -> bool {
+    matches!(
+        tag,
+        Tag::BlockQuote | Tag::List(_) | Tag::Item | Tag::FootnoteDefinition(_)
+    )
+}
+
 impl<'a> Iterator for EventIter<'a> {
     type Item = Event<'a>;
 
     fn next(&mut self) -> Option<Self::Item> {
         let next = self.p.next()?;
 
-        if let Event::Start(Tag::Image(LinkType::Inline, src, title)) = &next {
-            // TODO: Determine image size with `imagesize` crate
-            // TODO: There is no proper escaping library used here:
-            let imgtag = format!(
-                "<img src=\"{}\" title=\"{}\" width=\"\" height=\"\" />",
-                src.replace("\"", "&quot;"),
-                title.replace("\"", "&quot;")
-            );
+        if let Event::Start(Tag::Image(_, src, title)) = &next {
+            // Image sizes are added later, see `images::set_image_sizes`
+            let mut imgtag = String::from("<img src=\"");
+            escape_href(&mut imgtag, src).unwrap();
+            imgtag.push_str("\" alt=\"");
+            escape_html(&mut imgtag, &self.alt_text()).unwrap();
+            if !title.is_empty() {
+                imgtag.push_str("\" title=\"");
+                escape_html(&mut imgtag, title).unwrap();
+            }
+            imgtag.push_str("\" />");
             return Some(Event::Html(imgtag.into()));
         }
 
-        // match &next {
-        //     Event::Start(Tag::Image(l, n, d)) => {
-        //         println!("image {:?} {} {}", l, n, d);
-        //         return Some(Event::Html("IMG TAG HERE".into()));
-        //     },
-        //     _ => ()
-        // }
-
-        // if let &Event::Start(Tag::Image(t, _, _)) = &next {
-
-        //     return Some(next);
-        // }
-
-        // if let &Event::Start(Tag::CodeBlock(_)) = &next {
-        // 	// Codeblock time!
-        // 	let mut text_buf = String::new();
-        // 	let mut next = self.p.next();
-        // 	loop {
-        // 		if let Some(Event::Text(ref s)) = next {
-        // 			text_buf += s;
-        // 		} else {
-        // 			break;
-        // 		}
-        // 		next = self.p.next();
-        // 	}
-        // 	// let mut fmt = SyntectFormatter::new();
-        // 	match &next {
-        // 		Some(Event::End(Tag::CodeBlock(cb))) => {
-        //             println!("Foo");
-        // 			if let CodeBlockKind::Fenced(ref token) = cb {
-        // 				// fmt = fmt.token(token);
-        // 				println!("TTTTTTTTTTTTTTTTTTTTTTTTT {}", token);
-        // 			}
-        // 		},
-        // 		_ => panic!("Unexpected element inside codeblock mode {:?}", next),
-        // 	}
-        // 	// let formatted = fmt.highlight_snippet(&text_buf);
-        //     // return Some(Event::Html(formatted.into()));
-        //     return Some(Event::Html(text_buf.into()));
-        // }
+        if let Event::Start(Tag::Heading(level)) = next {
+            return Some(Event::Html(self.heading(level).into()));
+        }
+
+        if let Event::Start(Tag::CodeBlock(kind)) = &next {
+            let info = match kind {
+                CodeBlockKind::Fenced(info) => info.to_string(),
+                CodeBlockKind::Indented => String::new(),
+            };
+            return Some(Event::Html(highlight_code(&self.code(), &info).into()));
+        }
+
+        match &next {
+            Event::Start(tag) if is_container(tag) => self.depth += 1,
+            Event::End(tag) if is_container(tag) => self.depth -= 1,
+            _ => (),
+        }
+
+        match &next {
+            Event::Start(Tag::Paragraph) if self.first_paragraph.is_none() && self.depth == 0 => {
+                self.paragraph = Some(String::new())
+            }
+            Event::End(Tag::Paragraph) => {
+                self.in_word = false;
+                if let Some(text) = self.paragraph.take() {
+                    if !text.trim().is_empty() && text.trim() != "[TOC]" {
+                        self.first_paragraph = Some(text);
+                    }
+                }
+            }
+            Event::Text(text) => {
+                self.count_words(text);
+                if let Some(paragraph) = &mut self.paragraph {
+                    paragraph.push_str(text);
+                }
+            }
+            Event::Code(text) => {
+                if let Some(paragraph) = &mut self.paragraph {
+                    paragraph.push_str(text);
+                }
+            }
+            Event::SoftBreak | Event::HardBreak => {
+                self.in_word = false;
+                if let Some(paragraph) = &mut self.paragraph {
+                    paragraph.push(' ');
+                }
+            }
+
+            // Words continue over the inline tags, e.g. `**Ex**ample`
+            Event::End(Tag::Emphasis)
+            | Event::End(Tag::Strong)
+            | Event::End(Tag::Strikethrough)
+            | Event::End(Tag::Link(..)) => (),
+            Event::End(_) => self.in_word = false,
+            _ => (),
+        }
+
         Some(next)
     }
 }
@@ -76,8 +243,8 @@ impl<'a> Iterator for EventIter<'a> {
 //     # foo
 
 //     Lorem ipsum dolor sit amet, consectetuer adipiscing elit.
-//     Duis tincidunt erat in purus ullamcorper ultricies. Duis 
-//     lacinia aliquet dolor. 
+//     Duis tincidunt erat in purus ullamcorper ultricies. Duis
+//     lacinia aliquet dolor.
 
 //     ```bash
 //     # My code block
@@ -86,9 +253,9 @@ impl<'a> Iterator for EventIter<'a> {
 //     ```
 
 //     ![](./image.png \"with some title\")
-    
+
 //     Maecenas velit enim, eleifend a, tempor eu, mattis in, nisl.
-//     Maecenas ut orci. Sed egestas auctor sem. Curabitur vitae 
+//     Maecenas ut orci. Sed egestas auctor sem. Curabitur vitae
 //     pede vel nisl tristique commodo. Phasellus ut nisl. Cras massa.
 //      Suspendisse potenti. Vestibulum vitae augue. Mauris mauris sapien,
 //      aliquet vitae, tincidunt ac, volutpat eu, ante. Nunc sed quam.
@@ -107,16 +274,108 @@ impl<'a> Iterator for EventIter<'a> {
 //     println!("{}", html_output);
 // }
 
+#[derive(Debug)]
+pub struct MarkdownHtml {
+    pub html: String,
+    pub toc: Vec<TocHeading>,
+
+    /// Plain text of the first paragraph
+    pub first_paragraph: String,
+    pub word_count: usize,
+
+    /// Estimated reading time in minutes, at least one
+    pub reading_time: usize,
+}
 
-pub async fn markdown_to_html(markdown: &str) -> String {
+pub async fn markdown_to_html(markdown: &str) -> MarkdownHtml {
     let mut options = Options::empty();
     options.insert(Options::ENABLE_STRIKETHROUGH);
     options.insert(Options::ENABLE_FOOTNOTES);
     options.insert(Options::ENABLE_TABLES);
     let parser = Parser::new_ext(&markdown, options);
-    let ev_it = EventIter::new(parser);
+    let mut ev_it = EventIter::new(parser);
     let mut html_output = String::new();
-    html::push_html(&mut html_output, ev_it);
-    println!("{}", html_output);
-    html_output
+    html::push_html(&mut html_output, &mut ev_it);
+
+    let toc = heading_tree(ev_it.headings);
+    if html_output.contains(TOC_PLACEHOLDER) {
+        let inline_toc = if toc.is_empty() {
+            "".into()
+        } else {
+            format!("{}{}</nav>", INLINE_TOC_START, toc_html(&toc))
+        };
+        html_output = html_output.replace(TOC_PLACEHOLDER, &inline_toc);
+    }
+    MarkdownHtml {
+        html: html_output,
+        toc,
+        first_paragraph: ev_it.first_paragraph.unwrap_or_default(),
+        word_count: ev_it.word_count,
+        reading_time: ((ev_it.word_count + WORDS_PER_MINUTE - 1) / WORDS_PER_MINUTE).max(1),
+    }
+}
+
+/// Html without the anchor links of the headings, for the html shown outside
+/// of the document
+pub fn strip_heading_anchors(html: &str) -> String {
+    HEADING_ANCHOR.replace_all(html, "").into_owned()
+}
+
+#[cfg(test)]
+mod test_markdown_to_html {
+    use super::markdown_to_html;
+
+    #[async_std::test]
+    async fn test_heading_ids() {
+        let converted = markdown_to_html("## Intro\n\n## Intro\n\n### `Code` & more\n").await;
+        assert_eq!(
+            converted.html,
+            "<h2 id=\"intro\"><a class=\"anchor\" href=\"#intro\" aria-hidden=\"true\">#</a>Intro</h2>\n\
+             <h2 id=\"intro-1\"><a class=\"anchor\" href=\"#intro-1\" aria-hidden=\"true\">#</a>Intro</h2>\n\
+             <h3 id=\"code-more\"><a class=\"anchor\" href=\"#code-more\" aria-hidden=\"true\">#</a>\
+             <code>Code</code> &amp; more</h3>\n"
+        );
+        assert_eq!(converted.toc.len(), 2);
+        assert_eq!(converted.toc[1].children[0].title, "Code & more");
+    }
+
+    #[async_std::test]
+    async fn test_toc_placeholder() {
+        let converted = markdown_to_html("[TOC]\n\n## First\n\n```\n[TOC]\n```\n").await;
+        assert!(converted.html.starts_with(concat!(
+            "<nav class=\"toc\"><ul><li><a href=\"#first\">First</a></li></ul></nav>\n",
+            "<h2 id=\"first\">"
+        )));
+        assert!(converted.html.contains("[TOC]"));
+    }
+
+    #[async_std::test]
+    async fn test_first_paragraph() {
+        let converted = markdown_to_html(
+            "[TOC]\n\n## Intro\n\n![Image](image.png)\n\nFirst *emphasized*\n`code`\n\nSecond\n",
+        )
+        .await;
+        assert_eq!(converted.first_paragraph, "First emphasized code");
+
+        // Paragraphs inside quotes, lists and footnotes are skipped
+        let converted = markdown_to_html(
+            "> Quoted\n\n- Item\n\n  Loose item\n\n[^1]: Footnote\n\nTop level[^1]\n",
+        )
+        .await;
+        assert_eq!(converted.first_paragraph, "Top level");
+    }
+
+    #[async_std::test]
+    async fn test_word_count() {
+        let converted = markdown_to_html(
+            "## Two words\n\nOne **ex**ample, `code`\nand&nbsp;a [link](x.md)\n\n\
+             ```rust\nlet no = words;\n```\n\n<div>No words</div>\n\n- Item\n- Item\n",
+        )
+        .await;
+        assert_eq!(converted.word_count, 9);
+        assert_eq!(converted.reading_time, 1);
+
+        let converted = markdown_to_html(&"word ".repeat(401)).await;
+        assert_eq!(converted.reading_time, 3);
+    }
 }
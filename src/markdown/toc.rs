@@ -0,0 +1,104 @@
+use pulldown_cmark::escape::escape_html;
+use serde::{Deserialize, Serialize};
+
+/// Start of the table of contents in place of the `[TOC]` placeholder
+pub const INLINE_TOC_START: &str = "<nav class=\"toc\">";
+
+/// Heading of the table of contents, with the headings of the lower levels
+/// below it
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct TocHeading {
+    pub level: u32,
+
+    /// Id of the heading element, the target of the link
+    pub id: String,
+    pub title: String,
+    pub children: Vec<TocHeading>,
+}
+
+impl TocHeading {
+    /// Number of the headings in the table of contents
+    pub fn count(toc: &[TocHeading]) -> usize {
+        toc.iter()
+            .map(|heading| 1 + TocHeading::count(&heading.children))
+            .sum()
+    }
+}
+
+/// Nests the headings in document order below the preceding headings of a
+/// higher level
+pub fn heading_tree(headings: Vec<TocHeading>) -> Vec<TocHeading> {
+    fn insert(tree: &mut Vec<TocHeading>, heading: TocHeading) {
+        match tree.last_mut() {
+            Some(last) if last.level < heading.level => insert(&mut last.children, heading),
+            _ => tree.push(heading),
+        }
+    }
+
+    let mut tree = vec![];
+    for heading in headings {
+        insert(&mut tree, heading);
+    }
+    tree
+}
+
+/// Nested list of links to the headings, empty if there are none
+pub fn toc_html(toc: &[TocHeading]) -> String {
+    if toc.is_empty() {
+        return "".into();
+    }
+    let mut html = String::from("<ul>");
+    for heading in toc {
+        html.push_str("<li><a href=\"#");
+        escape_html(&mut html, &heading.id).unwrap();
+        html.push_str("\">");
+        escape_html(&mut html, &heading.title).unwrap();
+        html.push_str("</a>");
+        html.push_str(&toc_html(&heading.children));
+        html.push_str("</li>");
+    }
+    html.push_str("</ul>");
+    html
+}
+
+#[cfg(test)]
+mod test_toc {
+    use super::{heading_tree, toc_html, TocHeading};
+
+    fn heading(level: u32, id: &str) -> TocHeading {
+        TocHeading {
+            level,
+            id: id.into(),
+            title: id.to_uppercase(),
+            children: vec![],
+        }
+    }
+
+    #[test]
+    fn test_heading_tree() {
+        let tree = heading_tree(vec![
+            heading(2, "a"),
+            heading(3, "b"),
+            heading(4, "c"),
+            heading(3, "d"),
+            heading(2, "e"),
+        ]);
+        assert_eq!(tree.len(), 2);
+        assert_eq!(tree[0].children.len(), 2);
+        assert_eq!(tree[0].children[0].children, vec![heading(4, "c")]);
+        assert_eq!(TocHeading::count(&tree), 5);
+
+        // A document may start with a lower level heading
+        let tree = heading_tree(vec![heading(3, "a"), heading(2, "b")]);
+        assert_eq!(tree, vec![heading(3, "a"), heading(2, "b")]);
+    }
+
+    #[test]
+    fn test_toc_html() {
+        assert_eq!(toc_html(&[]), "");
+        assert_eq!(
+            toc_html(&heading_tree(vec![heading(2, "a"), heading(3, "b&c")])),
+            "<ul><li><a href=\"#a\">A</a><ul><li><a href=\"#b&amp;c\">B&amp;C</a></li></ul></li></ul>"
+        );
+    }
+}
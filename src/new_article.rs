@@ -0,0 +1,51 @@
+use async_std::fs;
+use async_std::path::PathBuf;
+use derive_more::From;
+
+use crate::slug::slugify;
+
+#[derive(Debug, From)]
+pub enum Error {
+    EmptySlug,
+    AlreadyExists(PathBuf),
+    IOError(std::io::Error),
+}
+
+fn article_template(title: &str) -> String {
+    format!(
+        "---\ntitle: {}\n---\n\n",
+        serde_json::to_string(title).unwrap()
+    )
+}
+
+/// Creates a new markdown file for the article, named by the slug of the title
+pub async fn new_article(article_dir: &PathBuf, title: &str) -> Result<PathBuf, Error> {
+    let slug = slugify(title);
+    if slug.is_empty() {
+        return Err(Error::EmptySlug);
+    }
+
+    let file = article_dir.join(format!("{}.md", slug));
+    if file.exists().await {
+        return Err(Error::AlreadyExists(file));
+    }
+    fs::create_dir_all(&article_dir).await?;
+    fs::write(&file, article_template(title)).await?;
+    Ok(file)
+}
+
+#[cfg(test)]
+mod test_new_article {
+    use super::article_template;
+
+    #[test]
+    fn test_article_template() {
+        let template = article_template("Colon: \"quoted\"");
+        assert_eq!(template, "---\ntitle: \"Colon: \\\"quoted\\\"\"\n---\n\n");
+
+        // Title must survive the YAML parsing of the frontmatter
+        let yaml: serde_yaml::Value =
+            serde_yaml::from_str(template.trim_matches(|c| c == '-' || c == '\n')).unwrap();
+        assert_eq!(yaml["title"].as_str(), Some("Colon: \"quoted\""));
+    }
+}
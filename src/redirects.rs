@@ -0,0 +1,174 @@
+use percent_encoding::percent_decode_str;
+use url::Url;
+
+use crate::config::SiteConfig;
+use crate::db::models::Article;
+
+/// Server path of the nginx rules, included in the server block of the old
+/// site
+pub const NGINX_PATH: &str = "/redirects.nginx.conf";
+
+/// Server path of the Apache rules
+pub const HTACCESS_PATH: &str = "/.htaccess";
+
+/// Server path of the Netlify rules
+pub const NETLIFY_PATH: &str = "/_redirects";
+
+/// Redirect from the path of the old site to the article
+#[derive(Debug, PartialEq)]
+pub struct Redirect {
+    /// Percent-encoded path of the old url, e.g. `/2019/01/post.html`
+    pub from: String,
+    pub to: Url,
+
+    /// Is the old url on the host of this site
+    pub same_host: bool,
+}
+
+/// Redirects of the articles with an old url
+///
+/// Old urls with a query, of the root, or with control characters in the
+/// decoded path can't be matched by all of the rules, and are skipped.
+pub fn redirects(articles: &[Article], site: &SiteConfig) -> Vec<Redirect> {
+    articles
+        .iter()
+        .filter_map(|article| {
+            let old_url = Url::parse(article.old_url.as_ref()?).ok()?;
+            let path = percent_decode_str(old_url.path()).decode_utf8().ok()?;
+            if old_url.query().is_some() || path == "/" || path.contains(char::is_control) {
+                return None;
+            }
+            Some(Redirect {
+                from: old_url.path().into(),
+                to: site.url(&article.server_path),
+                same_host: old_url.host() == site.base_url.host()
+                    && old_url.port_or_known_default() == site.base_url.port_or_known_default(),
+            })
+        })
+        .collect()
+}
+
+/// Quoted string of the nginx and Apache configurations
+fn quoted(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Decoded path of the redirect, nginx and Apache match the decoded paths
+fn decoded_from(redirect: &Redirect) -> String {
+    percent_decode_str(&redirect.from)
+        .decode_utf8_lossy()
+        .into_owned()
+}
+
+pub fn nginx_rules(redirects: &[Redirect]) -> String {
+    redirects
+        .iter()
+        .map(|r| {
+            format!(
+                "location = {} {{ return 301 {}; }}\n",
+                quoted(&decoded_from(r)),
+                quoted(r.to.as_str())
+            )
+        })
+        .collect()
+}
+
+pub fn htaccess_rules(redirects: &[Redirect]) -> String {
+    redirects
+        .iter()
+        .map(|r| format!("Redirect 301 {} {}\n", quoted(&decoded_from(r)), r.to))
+        .collect()
+}
+
+/// Netlify matches the percent-encoded paths
+pub fn netlify_rules(redirects: &[Redirect]) -> String {
+    redirects
+        .iter()
+        .map(|r| format!("{} {} 301\n", r.from, r.to))
+        .collect()
+}
+
+#[cfg(test)]
+mod test_redirects {
+    use super::{htaccess_rules, netlify_rules, nginx_rules, redirects, Redirect};
+    use crate::config::SiteConfig;
+    use crate::db::models::Article;
+    use url::Url;
+
+    fn article(old_url: Option<&str>) -> Article {
+        Article {
+            old_url: old_url.map(String::from),
+            ..Article::test("/articles/post01")
+        }
+    }
+
+    #[test]
+    fn test_redirects() {
+        let site = SiteConfig::default();
+        let redirects = redirects(
+            &[
+                article(Some("https://www.foo.com/path1/my%20post.html#comments")),
+                article(Some("http://127.0.0.1:8080/2019/post01")),
+                article(Some("https://www.foo.com/")),
+                article(Some("https://www.foo.com/index.php?p=1")),
+                article(Some("https://www.foo.com/new%0Aline")),
+                article(None),
+            ],
+            &site,
+        );
+
+        assert_eq!(
+            redirects,
+            vec![
+                Redirect {
+                    from: "/path1/my%20post.html".into(),
+                    to: Url::parse("http://127.0.0.1:8080/articles/post01").unwrap(),
+                    same_host: false,
+                },
+                Redirect {
+                    from: "/2019/post01".into(),
+                    to: Url::parse("http://127.0.0.1:8080/articles/post01").unwrap(),
+                    same_host: true,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rules() {
+        let redirects = vec![
+            Redirect {
+                from: "/2019/post01".into(),
+                to: Url::parse("https://example.com/articles/post01").unwrap(),
+                same_host: false,
+            },
+            Redirect {
+                from: "/my%20%22post%22".into(),
+                to: Url::parse("https://example.com/articles/post02").unwrap(),
+                same_host: false,
+            },
+        ];
+
+        assert_eq!(
+            nginx_rules(&redirects),
+            concat!(
+                "location = \"/2019/post01\" { return 301 \"https://example.com/articles/post01\"; }\n",
+                "location = \"/my \\\"post\\\"\" { return 301 \"https://example.com/articles/post02\"; }\n"
+            )
+        );
+        assert_eq!(
+            htaccess_rules(&redirects),
+            concat!(
+                "Redirect 301 \"/2019/post01\" https://example.com/articles/post01\n",
+                "Redirect 301 \"/my \\\"post\\\"\" https://example.com/articles/post02\n"
+            )
+        );
+        assert_eq!(
+            netlify_rules(&redirects),
+            concat!(
+                "/2019/post01 https://example.com/articles/post01 301\n",
+                "/my%20%22post%22 https://example.com/articles/post02 301\n"
+            )
+        );
+    }
+}
@@ -0,0 +1,136 @@
+use async_std::fs;
+use async_std::path::PathBuf;
+use derive_more::From;
+use futures::StreamExt;
+use std::collections::HashSet;
+
+use crate::db::{models::Resource, DbConnection, ResourceId};
+use crate::fingerprint::{file_hash, modified_on_disk};
+
+#[derive(Debug, From)]
+pub enum Error {
+    FileNameError,
+    IOError(std::io::Error),
+    DbError(crate::db::Error),
+}
+
+/// Is the file a markdown document instead of a resource
+pub fn is_markdown(path: &PathBuf) -> bool {
+    match path.extension() {
+        Some(ext) => matches!(
+            &ext.to_string_lossy().to_lowercase() as &str,
+            "md" | "markdown"
+        ),
+        None => false,
+    }
+}
+
+/// Server path with the content hash in the file name, e.g.
+/// `/resources/res01.e3b0c442.svg`
+fn fingerprinted_path(file: &PathBuf, hash: &str) -> Result<String, Error> {
+    let stem = file.file_stem().ok_or(Error::FileNameError)?;
+    let fingerprint = &hash[..8];
+    Ok(match file.extension() {
+        Some(ext) => format!(
+            "/resources/{}.{}.{}",
+            stem.to_string_lossy(),
+            fingerprint,
+            ext.to_string_lossy()
+        ),
+        None => format!("/resources/{}.{}", stem.to_string_lossy(), fingerprint),
+    })
+}
+
+/// Stores the resource with fingerprinted server path in the database
+///
+/// The file is hashed only if it has been modified since it was stored.
+pub async fn generate_resource_db(
+    resource_file: &PathBuf,
+    dbc: &DbConnection,
+) -> Result<Resource, Error> {
+    let local_path = resource_file.to_string_lossy().into_owned();
+    let modified_on_disk = modified_on_disk(&resource_file).await?;
+
+    let existing = Resource::get_by_local_path(&dbc, &local_path).await?;
+    if let Some(resource) = existing {
+        if resource.modified_on_disk == modified_on_disk {
+            return Ok(resource);
+        }
+    }
+
+    let hash = file_hash(&resource_file).await?;
+    let resource = Resource {
+        id: ResourceId::new(),
+        server_path: fingerprinted_path(&resource_file, &hash)?,
+        hash,
+        modified_on_disk,
+        local_path,
+    };
+    resource.save(&dbc).await?;
+    Ok(resource)
+}
+
+/// Copies the resource file to the output directory
+pub async fn copy_resource(resource: &Resource, output_dir: &PathBuf) -> Result<(), Error> {
+    let target = output_dir.join(resource.server_path.trim_start_matches('/'));
+
+    // Fingerprinted file with the same name has the same contents
+    if target.exists().await {
+        return Ok(());
+    }
+    if let Some(dir) = target.parent() {
+        fs::create_dir_all(dir).await?;
+    }
+    fs::copy(&resource.local_path, &target).await?;
+    Ok(())
+}
+
+/// Removes the resource files of the output directory that none of the
+/// resources is served from, e.g. the previous fingerprint of a changed file
+pub async fn clean_resource_outputs(
+    resources: &[Resource],
+    output_dir: &PathBuf,
+) -> Result<(), Error> {
+    let dir = output_dir.join("resources");
+    if !dir.is_dir().await {
+        return Ok(());
+    }
+
+    let server_paths = resources
+        .iter()
+        .map(|resource| resource.server_path.as_str())
+        .collect::<HashSet<_>>();
+    let mut entries = fs::read_dir(&dir).await?;
+    while let Some(entry) = entries.next().await {
+        let entry = entry?;
+        let server_path = format!("/resources/{}", entry.file_name().to_string_lossy());
+        if entry.file_type().await?.is_file() && !server_paths.contains(server_path.as_str()) {
+            fs::remove_file(entry.path()).await?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test_resources {
+    use super::{fingerprinted_path, is_markdown};
+
+    #[test]
+    fn test_is_markdown() {
+        assert!(is_markdown(&"./examples/articles/post01.md".into()));
+        assert!(!is_markdown(&"./examples/articles/res01.svg".into()));
+    }
+
+    #[test]
+    fn test_fingerprinted_path() {
+        let hash = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+        assert_eq!(
+            fingerprinted_path(&"./examples/articles/res01.svg".into(), hash).unwrap(),
+            "/resources/res01.e3b0c442.svg"
+        );
+        assert_eq!(
+            fingerprinted_path(&"./examples/articles/LICENSE".into(), hash).unwrap(),
+            "/resources/LICENSE.e3b0c442"
+        );
+    }
+}
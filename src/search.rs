@@ -0,0 +1,242 @@
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::config::SearchConfig;
+use crate::db::models::{Article, Page, Term};
+use crate::markdown::{strip_heading_anchors, INLINE_TOC_START};
+
+/// Server path of the search index
+pub const SEARCH_INDEX_PATH: &str = "/search.json";
+
+/// Search index for the browser, the documents are searched by their terms
+#[derive(Debug, Serialize)]
+struct SearchIndex {
+    documents: Vec<SearchDocument>,
+
+    /// Documents of each term by their position in `documents`, with the
+    /// score of the term in the document
+    terms: BTreeMap<String, Vec<(usize, u32)>>,
+}
+
+#[derive(Debug, Serialize)]
+struct SearchDocument {
+    title: String,
+    server_path: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    description: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+}
+
+static CODE: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?s)<pre[\s>].*?</pre>").unwrap());
+static TOC: Lazy<Regex> =
+    Lazy::new(|| Regex::new(&format!("(?s){}.*?</nav>", regex::escape(INLINE_TOC_START))).unwrap());
+static TAG: Lazy<Regex> = Lazy::new(|| Regex::new(r"<[^>]*>").unwrap());
+
+/// Text of the html, without the tags, code blocks, table of contents and
+/// heading anchors
+fn strip_html(html: &str) -> String {
+    let html = strip_heading_anchors(html);
+    let text = CODE.replace_all(&html, " ");
+    let text = TOC.replace_all(&text, " ");
+    TAG.replace_all(&text, " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Lowercase words of the text, except the stop words and single characters
+fn tokenize<'a>(
+    text: &'a str,
+    stop_words: &'a HashSet<String>,
+) -> impl Iterator<Item = String> + 'a {
+    text.split(|c: char| !c.is_alphanumeric())
+        .map(str::to_lowercase)
+        .filter(move |word| word.chars().count() > 1 && !stop_words.contains(word))
+}
+
+/// Terms of the document fields with their weighted scores, only the
+/// `max_terms` highest scoring terms are kept
+fn document_terms(
+    fields: &[(&str, u32)],
+    stop_words: &HashSet<String>,
+    max_terms: usize,
+) -> Vec<(String, u32)> {
+    let mut scores: HashMap<String, u32> = HashMap::new();
+    for (text, weight) in fields {
+        for term in tokenize(text, stop_words) {
+            *scores.entry(term).or_insert(0) += weight;
+        }
+    }
+
+    let mut terms: Vec<(String, u32)> = scores.into_iter().filter(|(_, s)| *s > 0).collect();
+
+    // Ties are sorted by the term, so the index only changes with the content
+    terms.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    terms.truncate(max_terms);
+    terms
+}
+
+/// Compact JSON search index of the articles and their terms, and the pages.
+/// Documents marked `noindex` are left out.
+pub fn search_index(
+    articles: &[(Article, Vec<Term>)],
+    pages: &[Page],
+    config: &SearchConfig,
+) -> Result<String, serde_json::Error> {
+    let stop_words: HashSet<String> = config
+        .stop_words
+        .iter()
+        .map(|word| word.to_lowercase())
+        .collect();
+
+    let article_documents =
+        articles
+            .iter()
+            .filter(|(article, _)| !article.noindex)
+            .map(|(article, terms)| {
+                let document = SearchDocument {
+                    title: article.title.clone(),
+                    server_path: article.server_path.clone(),
+                    description: article.description.clone(),
+                    tags: terms.iter().map(|term| term.name.clone()).collect(),
+                };
+                (document, article.html.as_str())
+            });
+    let page_documents = pages.iter().filter(|page| !page.noindex).map(|page| {
+        let document = SearchDocument {
+            title: page.title.clone(),
+            server_path: page.server_path.clone(),
+            description: "".into(),
+            tags: vec![],
+        };
+        (document, page.html.as_str())
+    });
+
+    let mut index = SearchIndex {
+        documents: vec![],
+        terms: BTreeMap::new(),
+    };
+    for (position, (document, html)) in article_documents.chain(page_documents).enumerate() {
+        let tags = document.tags.join(" ");
+        let body = strip_html(html);
+        let fields = [
+            (document.title.as_str(), config.title_weight),
+            (document.description.as_str(), config.description_weight),
+            (tags.as_str(), config.tags_weight),
+            (body.as_str(), config.body_weight),
+        ];
+        for (term, score) in document_terms(&fields, &stop_words, config.max_terms) {
+            index.terms.entry(term).or_default().push((position, score));
+        }
+        index.documents.push(document);
+    }
+    serde_json::to_string(&index)
+}
+
+#[cfg(test)]
+mod test_search {
+    use super::{search_index, strip_html};
+    use crate::config::SearchConfig;
+    use crate::db::{
+        models::{Article, Page, Term},
+        TermId,
+    };
+
+    fn article(server_path: &str, html: &str, noindex: bool) -> (Article, Vec<Term>) {
+        let article = Article {
+            title: "Async Rust".into(),
+            html: html.into(),
+            noindex,
+            description: "All about the futures".into(),
+            ..Article::test(server_path)
+        };
+        let terms = vec![Term {
+            id: TermId::new(),
+            kind: Term::TAG.into(),
+            name: "Rust".into(),
+            server_path: "/tags/rust".into(),
+        }];
+        (article, terms)
+    }
+
+    fn page(server_path: &str) -> Page {
+        Page {
+            title: "About".into(),
+            html: "<p>About the futures</p>".into(),
+            ..Page::test(server_path)
+        }
+    }
+
+    fn index(articles: &[(Article, Vec<Term>)], config: &SearchConfig) -> serde_json::Value {
+        let index = search_index(articles, &[page("/about")], config).unwrap();
+        serde_json::from_str(&index).unwrap()
+    }
+
+    #[test]
+    fn test_strip_html() {
+        assert_eq!(
+            strip_html("<p>Tom &amp; <a href=\"x\">Jerry</a></p><pre class=\"hl-code\"><code>let</code></pre>"),
+            " Tom &  Jerry   "
+        );
+        assert_eq!(
+            strip_html(concat!(
+                r##"<nav class="toc"><ul><li><a href="#intro">Intro</a></li></ul></nav>"##,
+                r##"<h2 id="intro"><a class="anchor" href="#intro" aria-hidden="true">#</a>Intro</h2>"##
+            )),
+            "  Intro "
+        );
+    }
+
+    #[test]
+    fn test_search_index() {
+        let index = index(
+            &[
+                article(
+                    "/articles/post01",
+                    "<p>The futures of the Rust, and the Rust</p>",
+                    false,
+                ),
+                article("/articles/hidden", "<p>Hidden</p>", true),
+            ],
+            &SearchConfig::default(),
+        );
+
+        assert_eq!(index["documents"][0]["server_path"], "/articles/post01");
+        assert_eq!(index["documents"][0]["tags"][0], "Rust");
+        assert_eq!(index["documents"][1]["server_path"], "/about");
+        assert!(index["documents"][1].get("description").is_none());
+
+        // Title 10, tags 5, body 1
+        assert_eq!(index["terms"]["rust"], serde_json::json!([[0, 17]]));
+        assert_eq!(
+            index["terms"]["futures"],
+            serde_json::json!([[0, 4], [1, 1]])
+        );
+        assert!(index["terms"].get("the").is_none());
+        assert!(index["terms"].get("hidden").is_none());
+    }
+
+    #[test]
+    fn test_search_index_bounds() {
+        let config = SearchConfig {
+            max_terms: 2,
+            body_weight: 0,
+            stop_words: vec!["Async".into()],
+            ..SearchConfig::default()
+        };
+        let index = index(
+            &[article("/articles/post01", "<p>Body only</p>", false)],
+            &config,
+        );
+
+        let terms = index["terms"].as_object().unwrap();
+        assert_eq!(terms.keys().collect::<Vec<_>>(), vec!["about", "rust"]);
+        assert_eq!(index["terms"]["rust"], serde_json::json!([[0, 15]]));
+        assert!(index["terms"].get("body").is_none());
+    }
+}
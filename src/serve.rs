@@ -0,0 +1,175 @@
+use async_std::channel::{unbounded, Receiver, Sender};
+use async_std::fs;
+use async_std::path::PathBuf;
+use async_std::sync::{Arc, Mutex};
+use async_std::task::JoinHandle;
+use derive_more::From;
+use percent_encoding::percent_decode_str;
+use std::path::{Component, Path};
+use tide::{http::mime, listener::Listener, Body, Request, Response, StatusCode};
+
+#[derive(Debug, From)]
+pub enum Error {
+    IOError(std::io::Error),
+}
+
+pub const LIVE_RELOAD_PATH: &str = "/__livereload";
+
+const LIVE_RELOAD_SCRIPT: &str = r#"<script>
+new EventSource("/__livereload").addEventListener("reload", function () {
+    location.reload();
+});
+</script>"#;
+
+/// Notifies the connected browsers to reload the page
+#[derive(Clone, Default)]
+pub struct LiveReload {
+    clients: Arc<Mutex<Vec<Sender<()>>>>,
+}
+
+impl LiveReload {
+    pub fn new() -> Self {
+        LiveReload::default()
+    }
+
+    async fn connect(&self) -> Receiver<()> {
+        let (sender, receiver) = unbounded();
+        self.clients.lock().await.push(sender);
+        receiver
+    }
+
+    pub async fn reload(&self) {
+        // Disconnected clients have dropped their receivers
+        let mut clients = self.clients.lock().await;
+        clients.retain(|client| client.try_send(()).is_ok());
+    }
+}
+
+#[derive(Clone)]
+struct State {
+    output_dir: PathBuf,
+    live_reload: LiveReload,
+}
+
+/// File or directory in the output directory for the request path, `None` if
+/// a decoded path segment could escape the output directory
+fn output_file(output_dir: &PathBuf, request_path: &str) -> Option<PathBuf> {
+    let mut file = output_dir.clone();
+    for part in request_path.split('/').filter(|p| !p.is_empty()) {
+        let part = percent_decode_str(part).decode_utf8().ok()?;
+        if part.contains('/') || part.contains('\\') {
+            return None;
+        }
+        let mut components = Path::new(&*part).components();
+        match (components.next(), components.next()) {
+            (Some(Component::Normal(_)), None) => file.push(&*part),
+            _ => return None,
+        }
+    }
+    if file.starts_with(output_dir) {
+        Some(file)
+    } else {
+        None
+    }
+}
+
+/// Adds the live reload script to the end of the HTML document
+fn inject_live_reload(html: &str) -> String {
+    match html.rfind("</body>") {
+        Some(pos) => format!("{}{}{}", &html[..pos], LIVE_RELOAD_SCRIPT, &html[pos..]),
+        None => format!("{}{}", html, LIVE_RELOAD_SCRIPT),
+    }
+}
+
+async fn serve_output(req: Request<State>) -> tide::Result {
+    let file = match output_file(&req.state().output_dir, req.url().path()) {
+        Some(file) => file,
+        None => return Ok(Response::new(StatusCode::BadRequest)),
+    };
+    let file = if file.is_dir().await {
+        file.join("index.html")
+    } else {
+        file
+    };
+    if !file.is_file().await {
+        return Ok(Response::new(StatusCode::NotFound));
+    }
+
+    if file.extension().map_or(false, |ext| ext == "html") {
+        let html = fs::read_to_string(&file).await?;
+        let mut res = Response::new(StatusCode::Ok);
+        res.set_content_type(mime::HTML);
+        res.set_body(inject_live_reload(&html));
+        return Ok(res);
+    }
+
+    let mut res = Response::new(StatusCode::Ok);
+    res.set_body(Body::from_file(&file).await?);
+    Ok(res)
+}
+
+/// Serves the output directory over HTTP, the pages reload when `live_reload`
+/// is notified
+pub async fn serve(
+    address: &str,
+    output_dir: &PathBuf,
+    live_reload: &LiveReload,
+) -> Result<JoinHandle<()>, Error> {
+    let mut app = tide::with_state(State {
+        output_dir: output_dir.clone(),
+        live_reload: live_reload.clone(),
+    });
+    app.at(LIVE_RELOAD_PATH).get(tide::sse::endpoint(
+        |req: Request<State>, sender| async move {
+            let reload = req.state().live_reload.connect().await;
+            while reload.recv().await.is_ok() {
+                sender.send("reload", "", None).await?;
+            }
+            Ok(())
+        },
+    ));
+    app.at("/").get(serve_output);
+    app.at("/*").get(serve_output);
+
+    // Binding fails early, e.g. if the address is in use
+    let mut listener = app.bind(address.to_owned()).await?;
+    println!("Serving at http://{}/", address);
+    Ok(async_std::task::spawn(async move {
+        if let Err(error) = listener.accept().await {
+            println!("{:?}", error);
+        }
+    }))
+}
+
+#[cfg(test)]
+mod test_serve {
+    use super::{inject_live_reload, output_file, LIVE_RELOAD_SCRIPT};
+    use async_std::path::PathBuf;
+
+    #[test]
+    fn test_output_file() {
+        let output_dir: PathBuf = ".out".into();
+        assert_eq!(
+            output_file(&output_dir, "/articles/post01/"),
+            Some(output_dir.join("articles").join("post01"))
+        );
+        assert_eq!(
+            output_file(&output_dir, "/resources/my%20image.png"),
+            Some(output_dir.join("resources").join("my image.png"))
+        );
+        assert_eq!(output_file(&output_dir, "/../secret.txt"), None);
+        assert_eq!(output_file(&output_dir, "/%2e%2e/secret.txt"), None);
+        assert_eq!(output_file(&output_dir, "/%2e%2e/"), None);
+        assert_eq!(output_file(&output_dir, "/%2Fetc%2Fpasswd"), None);
+        assert_eq!(output_file(&output_dir, "/articles/%2E/post01"), None);
+        assert_eq!(output_file(&output_dir, "/C:%5Cwindows"), None);
+    }
+
+    #[test]
+    fn test_inject_live_reload() {
+        assert_eq!(
+            inject_live_reload("<html><body>...</body></html>"),
+            format!("<html><body>...{}</body></html>", LIVE_RELOAD_SCRIPT)
+        );
+    }
+}
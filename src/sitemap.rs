@@ -0,0 +1,80 @@
+use chrono::NaiveDateTime;
+
+use crate::config::SiteConfig;
+use crate::db::models::{Article, Page};
+use crate::feeds::{escape_xml, rfc3339};
+
+/// Server path of the sitemap
+pub const SITEMAP_PATH: &str = "/sitemap.xml";
+
+/// Server path of the robots.txt
+pub const ROBOTS_PATH: &str = "/robots.txt";
+
+/// Sitemap of the articles and pages, except the ones marked `noindex`
+pub fn sitemap(articles: &[Article], pages: &[Page], site: &SiteConfig) -> String {
+    let documents: Vec<(&str, &NaiveDateTime)> = articles
+        .iter()
+        .filter(|article| !article.noindex)
+        .map(|article| (article.server_path.as_str(), &article.modified))
+        .chain(
+            pages
+                .iter()
+                .filter(|page| !page.noindex)
+                .map(|page| (page.server_path.as_str(), &page.modified)),
+        )
+        .collect();
+
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str("<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n");
+    for (server_path, modified) in documents {
+        xml.push_str(&format!(
+            "<url><loc>{}</loc><lastmod>{}</lastmod></url>\n",
+            escape_xml(site.url(server_path).as_str()),
+            rfc3339(modified)
+        ));
+    }
+    xml.push_str("</urlset>\n");
+    xml
+}
+
+/// Allows crawling everything, the documents opt out with the robots meta tag
+pub fn robots_txt(site: &SiteConfig) -> String {
+    format!(
+        "User-agent: *\nAllow: /\n\nSitemap: {}\n",
+        site.url(SITEMAP_PATH)
+    )
+}
+
+#[cfg(test)]
+mod test_sitemap {
+    use super::{robots_txt, sitemap};
+    use crate::config::SiteConfig;
+    use crate::db::models::Page;
+
+    fn page(server_path: &str, noindex: bool) -> Page {
+        Page {
+            noindex,
+            ..Page::test(server_path)
+        }
+    }
+
+    #[test]
+    fn test_sitemap() {
+        let xml = sitemap(
+            &[],
+            &[page("/about", false), page("/private", true)],
+            &SiteConfig::default(),
+        );
+        assert!(xml.contains(
+            "<url><loc>http://127.0.0.1:8080/about</loc><lastmod>2021-01-01T12:00:00Z</lastmod></url>"
+        ));
+        assert!(!xml.contains("/private"));
+    }
+
+    #[test]
+    fn test_robots_txt() {
+        assert!(robots_txt(&SiteConfig::default())
+            .ends_with("Sitemap: http://127.0.0.1:8080/sitemap.xml\n"));
+    }
+}
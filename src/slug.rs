@@ -0,0 +1,25 @@
+/// Lowercase URL slug of the text, e.g. `Hello, World!` becomes `hello-world`
+pub fn slugify(text: &str) -> String {
+    let mut slug = String::with_capacity(text.len());
+    for c in text.chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_end_matches('-').to_owned()
+}
+
+#[cfg(test)]
+mod test_slugify {
+    use super::slugify;
+
+    #[test]
+    fn test_slugify() {
+        assert_eq!(slugify("Hello, World!"), "hello-world");
+        assert_eq!(slugify("  Rust & async-std  "), "rust-async-std");
+        assert_eq!(slugify("Äänekoski 2021"), "äänekoski-2021");
+        assert_eq!(slugify("!!!"), "");
+    }
+}
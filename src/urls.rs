@@ -1,7 +1,9 @@
+use std::collections::{HashMap, HashSet};
 use std::io::ErrorKind;
 
 use async_std::path::PathBuf;
 use derive_more::From;
+use percent_encoding::percent_decode_str;
 use regex::{Captures, Regex};
 use url::Url;
 
@@ -13,6 +15,7 @@ pub enum Error {
     FileNotFound(PathBuf),
     CanonicalizationFailed(PathBuf, std::io::Error),
     UrlParsingFailed(String, url::ParseError),
+    UnknownFileUrl(String),
 }
 
 #[derive(Debug)]
@@ -51,10 +54,21 @@ pub async fn convert_html_urls(
         let end = rmatch.end();
         let value = rmatch.as_str();
 
+        // Links to the headings of the same document stay as is
+        if value.starts_with('#') {
+            continue;
+        }
+
         // If url location contains ':' it's external url, otherwise relative path
         let url = if value.contains(':') {
             Url::parse(value).map_err(|er| Error::UrlParsingFailed(value.into(), er))?
         } else {
+            let (value, fragment) = match value.find('#') {
+                Some(i) => (&value[..i], Some(&value[i + 1..])),
+                None => (value, None),
+            };
+            // Paths of the markdown are percent-encoded, e.g. `kuva-%C3%A4.png`
+            let value = percent_decode_str(value).decode_utf8_lossy();
             let path = if let Some(rest) = value.strip_prefix('/') {
                 // Relative to root path
                 root_path.join(rest.replace("/", &std::path::MAIN_SEPARATOR.to_string()))
@@ -72,7 +86,10 @@ pub async fn convert_html_urls(
             }
 
             let path = full_path.clone();
-            Url::from_file_path(full_path).map_err(|_| Error::UrlCreationFailed(path))?
+            let mut url =
+                Url::from_file_path(full_path).map_err(|_| Error::UrlCreationFailed(path))?;
+            url.set_fragment(fragment);
+            url
         };
         res.html.insert_str(0, &html[end..last_pos]);
         res.html.insert_str(0, &url.to_string());
@@ -84,13 +101,56 @@ pub async fn convert_html_urls(
     Ok(res)
 }
 
+/// All file:// urls of the html, without their fragments
+pub fn file_urls(html: &str) -> Vec<Url> {
+    let reg = Regex::new(r##" (href|src)="(file://[^"#]*)(#[^"]*)?""##).unwrap();
+    reg.captures_iter(html)
+        .filter_map(|caps| Url::parse(&caps[2]).ok())
+        .collect()
+}
+
+/// Replaces the file:// urls with the paths they are served from
+///
+/// Every file:// url must be found from `server_paths`, or from `unpublished`
+/// when it points to a document that is not published yet. The attributes of
+/// the unpublished urls are left out, so that the links are shown as plain
+/// text. Fragments of the urls are kept, e.g. links to the headings of other
+/// articles.
+pub fn replace_file_urls(
+    html: &str,
+    server_paths: &HashMap<String, String>,
+    unpublished: &HashSet<String>,
+) -> Result<String, Error> {
+    let reg = Regex::new(r##" (href|src)="(file://[^"#]*)(#[^"]*)?""##).unwrap();
+    if let Some(caps) = reg
+        .captures_iter(html)
+        .find(|caps| !server_paths.contains_key(&caps[2]) && !unpublished.contains(&caps[2]))
+    {
+        return Err(Error::UnknownFileUrl(caps[2].to_owned()));
+    }
+
+    Ok(reg
+        .replace_all(html, |caps: &Captures| match server_paths.get(&caps[2]) {
+            Some(server_path) => format!(
+                r#" {}="{}{}""#,
+                &caps[1],
+                server_path,
+                caps.get(3).map_or("", |fragment| fragment.as_str())
+            ),
+            None => "".into(),
+        })
+        .into_owned())
+}
+
 #[cfg(test)]
 mod test_normalize_html_relative_urls {
     use super::{convert_html_urls, Error};
+    use crate::normalize::normalize;
+    use async_std::{fs, path::PathBuf};
 
     #[async_std::test]
     async fn test() {
-        let html = r#"first
+        let html = r##"first
             <!-- Relative to root path -->
             <link href="/style.css" />
 
@@ -98,12 +158,14 @@ mod test_normalize_html_relative_urls {
             <a href="./post02.md">...</a>
             <a href="../pages/example.md">...</a>
             <img src="res01.svg" />
+            <a href="post02.md#intro">...</a>
+            <a href="#intro">...</a>
 
             <!-- Full urls -->
             <a href="https://www.example.com">...</a>
-            end"#;
+            end"##;
 
-        let expect_html = r#"first
+        let expect_html = r##"first
             <!-- Relative to root path -->
             <link href="file:///C:/Source/Rust/cianticblog/examples/layout/style.css" />
 
@@ -111,10 +173,12 @@ mod test_normalize_html_relative_urls {
             <a href="file:///C:/Source/Rust/cianticblog/examples/articles/post02.md">...</a>
             <a href="file:///C:/Source/Rust/cianticblog/examples/pages/example.md">...</a>
             <img src="file:///C:/Source/Rust/cianticblog/examples/articles/res01.svg" />
+            <a href="file:///C:/Source/Rust/cianticblog/examples/articles/post02.md#intro">...</a>
+            <a href="#intro">...</a>
 
             <!-- Full urls -->
             <a href="https://www.example.com/">...</a>
-            end"#;
+            end"##;
 
         let value = convert_html_urls(
             html,
@@ -124,7 +188,7 @@ mod test_normalize_html_relative_urls {
         .await
         .unwrap();
         assert_eq!(value.html, expect_html);
-        assert_eq!(value.urls.len(), 5);
+        assert_eq!(value.urls.len(), 6);
     }
 
     // from `matches` crate
@@ -137,6 +201,23 @@ mod test_normalize_html_relative_urls {
         }
     }
 
+    #[async_std::test]
+    async fn test_non_ascii_file_name() {
+        let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("target")
+            .join("test_urls");
+        fs::create_dir_all(&dir).await.unwrap();
+        fs::write(dir.join("kuva ä.png"), "").await.unwrap();
+
+        // Markdown writes the paths percent-encoded
+        let html = r#"<img src="kuva%20%C3%A4.png" alt="Kuva" />"#;
+        let value = convert_html_urls(html, &dir, &dir).await.unwrap();
+        assert_eq!(
+            PathBuf::from(value.urls[0].to_file_path().unwrap()),
+            normalize(&dir).await.unwrap().join("kuva ä.png")
+        );
+    }
+
     #[async_std::test]
     async fn test_urlparsing_failure() {
         let html = r#"
@@ -171,3 +252,75 @@ mod test_normalize_html_relative_urls {
         );
     }
 }
+
+#[cfg(test)]
+mod test_replace_file_urls {
+    use super::{file_urls, replace_file_urls, Error};
+    use std::collections::{HashMap, HashSet};
+
+    fn server_paths() -> HashMap<String, String> {
+        let mut server_paths = HashMap::new();
+        server_paths.insert(
+            "file:///blog/articles/res01.svg".to_owned(),
+            "/resources/res01.e3b0c442.svg".to_owned(),
+        );
+        server_paths.insert(
+            "file:///blog/articles/post02.md".to_owned(),
+            "/articles/post02".to_owned(),
+        );
+        server_paths
+    }
+
+    #[test]
+    fn test() {
+        let html = r##"<img src="file:///blog/articles/res01.svg" alt="" />
+            <a href="file:///blog/articles/post02.md">...</a>
+            <a href="file:///blog/articles/post02.md#intro">...</a>
+            <a href="#intro">...</a>
+            <a href="https://www.example.com/">...</a>"##;
+
+        assert_eq!(
+            replace_file_urls(html, &server_paths(), &HashSet::new()).unwrap(),
+            r##"<img src="/resources/res01.e3b0c442.svg" alt="" />
+            <a href="/articles/post02">...</a>
+            <a href="/articles/post02#intro">...</a>
+            <a href="#intro">...</a>
+            <a href="https://www.example.com/">...</a>"##
+        );
+    }
+
+    #[test]
+    fn test_file_urls() {
+        let html = r#"<img src="file:///blog/articles/res01.svg" alt="" />
+            <a href="https://www.example.com/">...</a>"#;
+
+        let urls = file_urls(html);
+        assert_eq!(urls.len(), 1);
+        assert_eq!(urls[0].as_str(), "file:///blog/articles/res01.svg");
+    }
+
+    #[test]
+    fn test_unknown_file_url() {
+        let html = r#"<a href="file:///blog/articles/post02.md">...</a>
+            <a href="file:///blog/articles/missing.md">...</a>"#;
+
+        match replace_file_urls(html, &server_paths(), &HashSet::new()) {
+            Err(Error::UnknownFileUrl(url)) => assert_eq!(url, "file:///blog/articles/missing.md"),
+            other => panic!("expected unknown file url, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_unpublished_file_url() {
+        let html = r#"<a href="file:///blog/articles/post02.md">...</a>
+            <a href="file:///blog/drafts/post03.md">...</a>"#;
+
+        let mut unpublished = HashSet::new();
+        unpublished.insert("file:///blog/drafts/post03.md".to_owned());
+        assert_eq!(
+            replace_file_urls(html, &server_paths(), &unpublished).unwrap(),
+            r#"<a href="/articles/post02">...</a>
+            <a>...</a>"#
+        );
+    }
+}
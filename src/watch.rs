@@ -0,0 +1,264 @@
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::channel;
+use std::time::Duration;
+
+use async_std::channel::Sender;
+use async_std::task::JoinHandle;
+use derive_more::From;
+use notify::{watcher, DebouncedEvent, RecursiveMode, Watcher};
+
+use crate::normalize::normalize_sync;
+use crate::{FilesChange, GenerateParams, Message};
+
+#[derive(Debug, From)]
+pub enum Error {
+    IOError(std::io::Error),
+    NotifyError(notify::Error),
+}
+
+/// Watched directories, normalized so that they can be compared to the paths
+/// of the events
+struct WatchedDirs {
+    article_dir: PathBuf,
+    pages_dir: PathBuf,
+    root_dir: PathBuf,
+    output_dir: PathBuf,
+    db_file: PathBuf,
+}
+
+/// Absolute path, even if the file does not exist yet
+fn absolute(path: &async_std::path::PathBuf) -> Result<PathBuf, std::io::Error> {
+    let path: PathBuf = path.clone().into();
+    normalize_sync(&path).or_else(|_| Ok(std::env::current_dir()?.join(path)))
+}
+
+impl WatchedDirs {
+    fn new(params: &GenerateParams) -> Result<Self, Error> {
+        Ok(WatchedDirs {
+            article_dir: absolute(&params.article_dir)?,
+            pages_dir: absolute(&params.pages_dir)?,
+            root_dir: absolute(&params.root_dir)?,
+            output_dir: absolute(&params.output_dir)?,
+            db_file: absolute(&params.db_file)?,
+        })
+    }
+
+    /// Is the file written by the generator itself
+    fn is_generated(&self, path: &Path) -> bool {
+        path.starts_with(&self.output_dir)
+            || path
+                .to_string_lossy()
+                .starts_with(&*self.db_file.to_string_lossy())
+    }
+
+    /// Is the file inside a hidden directory of the root, e.g. `.git`
+    fn in_hidden_dir(&self, path: &Path) -> bool {
+        let relative = match path.parent().map(|dir| dir.strip_prefix(&self.root_dir)) {
+            Some(Ok(relative)) => relative,
+            _ => return false,
+        };
+        relative
+            .components()
+            .any(|dir| dir.as_os_str().to_string_lossy().starts_with('.'))
+    }
+}
+
+fn is_markdown(path: &Path) -> bool {
+    path.extension().map_or(false, |ext| ext == "md")
+}
+
+/// Editor swap files, backups and other hidden files are not part of the site
+fn is_hidden(path: &Path) -> bool {
+    path.file_name().map_or(true, |name| {
+        let name = name.to_string_lossy();
+        name.starts_with('.') || name.ends_with('~')
+    })
+}
+
+/// Groups the debounced events to changes of articles, pages and other files
+fn files_changes(dirs: &WatchedDirs, events: Vec<DebouncedEvent>) -> Vec<FilesChange> {
+    let mut changed = vec![];
+    let mut removed = vec![];
+    for event in events {
+        match event {
+            DebouncedEvent::Create(path) | DebouncedEvent::Write(path) => changed.push(path),
+            DebouncedEvent::Remove(path) => removed.push(path),
+            DebouncedEvent::Rename(from, to) => {
+                removed.push(from);
+                changed.push(to);
+            }
+            _ => (),
+        }
+    }
+
+    let mut articles_changed = vec![];
+    let mut pages_changed = vec![];
+    let mut articles_deleted = vec![];
+    let mut pages_deleted = vec![];
+    let mut root_files = vec![];
+
+    for (path, exists) in changed
+        .into_iter()
+        .map(|p| (p, true))
+        .chain(removed.into_iter().map(|p| (p, false)))
+    {
+        if is_hidden(&path) || dirs.in_hidden_dir(&path) || dirs.is_generated(&path) {
+            continue;
+        }
+        let is_article = path.starts_with(&dirs.article_dir);
+        let is_page = path.starts_with(&dirs.pages_dir);
+        if !is_markdown(&path) || !(is_article || is_page) {
+            // Resources and layout files may be used by any of the documents
+            if path.is_dir() {
+                continue;
+            }
+            if path.starts_with(&dirs.root_dir) || is_article || is_page {
+                root_files.push(path.into());
+            }
+            continue;
+        }
+
+        let path = if exists {
+            normalize_sync(&path).unwrap_or(path)
+        } else {
+            path
+        };
+        match (is_article, exists) {
+            (true, true) => articles_changed.push(path.into()),
+            (true, false) => articles_deleted.push(path.into()),
+            (false, true) => pages_changed.push(path.into()),
+            (false, false) => pages_deleted.push(path.into()),
+        }
+    }
+
+    let mut changes = vec![];
+    if !root_files.is_empty() {
+        changes.push(FilesChange::RootChanged { root_files });
+    }
+    if !articles_changed.is_empty() {
+        changes.push(FilesChange::ArticlesChanged {
+            files: articles_changed,
+        });
+    }
+    if !pages_changed.is_empty() {
+        changes.push(FilesChange::PagesChanged {
+            files: pages_changed,
+        });
+    }
+    if !articles_deleted.is_empty() {
+        changes.push(FilesChange::ArticlesDeleted {
+            files: articles_deleted,
+        });
+    }
+    if !pages_deleted.is_empty() {
+        changes.push(FilesChange::PagesDeleted {
+            files: pages_deleted,
+        });
+    }
+    changes
+}
+
+/// Watches the article, page and root directories, and sends the changed
+/// files as `Message::Changes`
+///
+/// Root directory is watched recursively for the layout files and resources,
+/// the changes of the output and the database inside it are ignored. Article
+/// and page directories are watched separately only if they are outside of
+/// the root.
+pub fn watch(params: &GenerateParams, sender: &Sender<Message>) -> Result<JoinHandle<()>, Error> {
+    let dirs = WatchedDirs::new(&params)?;
+    let (tx, rx) = channel();
+    let mut watcher = watcher(tx, Duration::from_millis(300))?;
+    watcher.watch(&dirs.root_dir, RecursiveMode::Recursive)?;
+    for dir in &[&dirs.article_dir, &dirs.pages_dir] {
+        if !dir.starts_with(&dirs.root_dir) {
+            watcher.watch(dir, RecursiveMode::Recursive)?;
+        }
+    }
+
+    let sender = sender.clone();
+    Ok(async_std::task::spawn_blocking(move || {
+        // Watching stops when the watcher is dropped
+        let _watcher = watcher;
+        while let Ok(event) = rx.recv() {
+            let mut events = vec![event];
+            events.extend(rx.try_iter());
+            let changes = files_changes(&dirs, events);
+            if changes.is_empty() {
+                continue;
+            }
+            if async_std::task::block_on(sender.send(Message::Changes(changes))).is_err() {
+                break;
+            }
+        }
+    }))
+}
+
+#[cfg(test)]
+mod test_watch {
+    use super::{files_changes, WatchedDirs};
+    use crate::FilesChange;
+    use async_std::path::PathBuf;
+    use notify::DebouncedEvent;
+
+    fn dirs() -> WatchedDirs {
+        WatchedDirs {
+            article_dir: "/blog/articles".into(),
+            pages_dir: "/blog/pages".into(),
+            root_dir: "/blog".into(),
+            output_dir: "/blog/.out".into(),
+            db_file: "/blog/.cache.db".into(),
+        }
+    }
+
+    #[test]
+    fn test_layout_changes() {
+        let changes = files_changes(
+            &dirs(),
+            vec![DebouncedEvent::Write("/blog/layout/style.css".into())],
+        );
+
+        match &changes[..] {
+            [FilesChange::RootChanged { root_files }] => {
+                assert_eq!(root_files, &vec![PathBuf::from("/blog/layout/style.css")])
+            }
+            other => panic!("expected root change, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_files_changes() {
+        let changes = files_changes(
+            &dirs(),
+            vec![
+                DebouncedEvent::Remove("/blog/articles/post01.md".into()),
+                DebouncedEvent::Remove("/blog/pages/about.md".into()),
+                DebouncedEvent::Write("/blog/style.css".into()),
+                DebouncedEvent::Write("/blog/.out/index.html".into()),
+                DebouncedEvent::Write("/blog/.cache.db-journal".into()),
+                DebouncedEvent::Write("/blog/articles/.post01.md.swp".into()),
+                DebouncedEvent::Write("/blog/.git/index".into()),
+            ],
+        );
+
+        assert_eq!(changes.len(), 3);
+        match &changes[0] {
+            FilesChange::RootChanged { root_files } => {
+                assert_eq!(root_files, &vec![PathBuf::from("/blog/style.css")])
+            }
+            other => panic!("expected root change, got {:?}", other),
+        }
+        match &changes[1] {
+            FilesChange::ArticlesDeleted { files } => {
+                assert_eq!(files, &vec![PathBuf::from("/blog/articles/post01.md")])
+            }
+            other => panic!("expected deleted articles, got {:?}", other),
+        }
+        match &changes[2] {
+            FilesChange::PagesDeleted { files } => {
+                assert_eq!(files, &vec![PathBuf::from("/blog/pages/about.md")])
+            }
+            other => panic!("expected deleted pages, got {:?}", other),
+        }
+    }
+}